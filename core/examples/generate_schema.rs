@@ -0,0 +1,26 @@
+//! Writes the published JSON Schemas to `schemas/`:
+//!
+//! ```bash
+//! cargo run -p proving_service_core --features schema --example generate_schema
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use proving_service_core::schema::published_schemas;
+use schemars::schema::RootSchema;
+
+fn write_schema(file_name: &str, schema: RootSchema) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../schemas")
+        .join(file_name);
+    let json = serde_json::to_string_pretty(&schema).expect("Failed to serialize schema");
+    fs::write(&path, json + "\n").expect("Failed to write schema");
+    println!("Wrote {}", path.display());
+}
+
+fn main() {
+    for (file_name, schema) in published_schemas() {
+        write_schema(file_name, schema);
+    }
+}
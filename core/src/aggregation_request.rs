@@ -0,0 +1,17 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::product_footprint::ProductProof;
+
+/// Request to aggregate many upstream proofs into a single receipt.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AggregationRequest {
+    /// Id reported in the resulting `ProductProof`.
+    pub productFootprintId: String,
+    pub proofs: Vec<ProductProof>,
+    /// Number of receipts verified per aggregation guest run.
+    #[serde(default)]
+    pub batchSize: Option<usize>,
+}
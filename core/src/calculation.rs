@@ -0,0 +1,489 @@
+//! PCF calculation of the proofing guest, callable natively on the host.
+//!
+//! Results are bit for bit the same in guest and host as long as the order of
+//! floating point operations is kept.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest as _, Sha256};
+
+use crate::hoc_toc_data::{HocData, TocData};
+use crate::product_footprint::TCE;
+use crate::proofing_document::{ProofingDocument, SensorData, TceSensorData};
+
+/// Why the PCF of a document cannot be calculated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalculationError {
+    MissingExtension,
+    UnknownToc {
+        tce_id: String,
+        toc_id: String,
+    },
+    UnknownHoc {
+        tce_id: String,
+        hoc_id: String,
+    },
+    InvalidIntensity {
+        tce_id: String,
+        id: String,
+        intensity: String,
+    },
+    CommitmentMismatch {
+        tce_id: String,
+    },
+    /// Mass or distance of a TCE is negative, not finite or implausibly large.
+    InvalidValue {
+        tce_id: String,
+        field: &'static str,
+        value: f64,
+    },
+    /// The summed PCF of the upstream proofs is negative or not finite.
+    InvalidUpstreamPcf {
+        pcf: f64,
+    },
+    /// The sum overflowed, e.g. because of huge upstream PCFs.
+    NonFinitePcf,
+}
+
+impl CalculationError {
+    /// Stable identifier, e.g. for golden files.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CalculationError::MissingExtension => "missingExtension",
+            CalculationError::UnknownToc { .. } => "unknownToc",
+            CalculationError::UnknownHoc { .. } => "unknownHoc",
+            CalculationError::InvalidIntensity { .. } => "invalidIntensity",
+            CalculationError::CommitmentMismatch { .. } => "commitmentMismatch",
+            CalculationError::InvalidValue { .. } => "invalidValue",
+            CalculationError::InvalidUpstreamPcf { .. } => "invalidUpstreamPcf",
+            CalculationError::NonFinitePcf => "nonFinitePcf",
+        }
+    }
+
+    /// The offending TCE, unless the error concerns the whole document.
+    pub fn tce_id(&self) -> Option<&str> {
+        match self {
+            CalculationError::UnknownToc { tce_id, .. }
+            | CalculationError::UnknownHoc { tce_id, .. }
+            | CalculationError::InvalidIntensity { tce_id, .. }
+            | CalculationError::CommitmentMismatch { tce_id }
+            | CalculationError::InvalidValue { tce_id, .. } => Some(tce_id),
+            CalculationError::MissingExtension
+            | CalculationError::InvalidUpstreamPcf { .. }
+            | CalculationError::NonFinitePcf => None,
+        }
+    }
+}
+
+impl fmt::Display for CalculationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalculationError::MissingExtension => write!(f, "document has no iLEAP extension"),
+            CalculationError::UnknownToc { tce_id, toc_id } => {
+                write!(f, "TCE {} references unknown TOC {}", tce_id, toc_id)
+            }
+            CalculationError::UnknownHoc { tce_id, hoc_id } => {
+                write!(f, "TCE {} references unknown HOC {}", tce_id, hoc_id)
+            }
+            CalculationError::InvalidIntensity { id, intensity, .. } => write!(
+                f,
+                "intensity '{}' of {} is not a number between 0 and {}",
+                intensity, id, MAX_INTENSITY
+            ),
+            CalculationError::CommitmentMismatch { tce_id } => {
+                write!(f, "sensor data commitment of TCE {} does not match", tce_id)
+            }
+            CalculationError::InvalidValue {
+                tce_id,
+                field,
+                value,
+            } => write!(
+                f,
+                "{} {} of TCE {} is not between 0 and {}",
+                field,
+                value,
+                tce_id,
+                max_value(field)
+            ),
+            CalculationError::InvalidUpstreamPcf { pcf } => {
+                write!(f, "PCF {} of the upstream proofs is invalid", pcf)
+            }
+            CalculationError::NonFinitePcf => write!(f, "PCF is not a finite number"),
+        }
+    }
+}
+
+/// Result of [`calculate_pcf`].
+#[derive(Debug, Clone)]
+pub struct PcfCalculation<'a> {
+    pub pcf: f64,
+    /// Sensor data of the counted transport legs, its commitment was checked.
+    pub sensor_data: Vec<&'a TceSensorData>,
+}
+
+/// Upper bound of a TCE mass in kg, above the largest bulk carriers.
+pub const MAX_MASS: f64 = 1e9;
+/// Upper bound of a transport leg distance in km.
+pub const MAX_DISTANCE: f64 = 1e5;
+/// Upper bound of a TOC or HOC intensity, far above air freight.
+pub const MAX_INTENSITY: f64 = 1e6;
+
+fn max_value(field: &str) -> f64 {
+    match field {
+        "mass" => MAX_MASS,
+        "distance" => MAX_DISTANCE,
+        _ => MAX_INTENSITY,
+    }
+}
+
+/// Parses a `co2eIntensityWTW` value like `"85 gCO2e/tkm"`: the first space
+/// separated token has to be a number between 0 and [`MAX_INTENSITY`].
+pub fn parse_intensity(intensity: &str) -> Option<f64> {
+    intensity
+        .split(' ')
+        .next()?
+        .parse::<f64>()
+        .ok()
+        .filter(|value| (0.0..=MAX_INTENSITY).contains(value))
+}
+
+/// Checks the invariants of the values the guest multiplies: finite,
+/// non-negative and at most [`MAX_MASS`] respectively [`MAX_DISTANCE`].
+pub fn check_tce_values(tce: &TCE) -> Result<(), CalculationError> {
+    let distance = tce.distance.as_ref().map(|d| ("distance", d.actual));
+    for (field, value) in [("mass", tce.mass)].into_iter().chain(distance) {
+        if !(0.0..=max_value(field)).contains(&value) {
+            return Err(CalculationError::InvalidValue {
+                tce_id: tce.tceId.clone(),
+                field,
+                value,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Commitment to sensor data: base64(SHA-256(sensorData JSON + salt)).
+pub fn sensor_data_commitment(sensor_data: &SensorData, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(sensor_data).unwrap_or_default());
+    hasher.update(salt.as_bytes());
+    general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// Adds the emissions of the document's TCEs to `upstream_pcf`, the sum of the
+/// PCFs of verified upstream proofs.
+///
+/// Transport legs emit `mass * intensity * distance`, hub stops `mass *
+/// intensity`. Transport legs without a distance are skipped. Values outside
+/// the bounds of [`check_tce_values`] and [`parse_intensity`] are rejected.
+pub fn calculate_pcf(
+    document: &ProofingDocument,
+    upstream_pcf: f64,
+) -> Result<PcfCalculation<'_>, CalculationError> {
+    if !(upstream_pcf.is_finite() && upstream_pcf >= 0.0) {
+        return Err(CalculationError::InvalidUpstreamPcf { pcf: upstream_pcf });
+    }
+    let mut pcf = upstream_pcf;
+    let mut sensor_data = Vec::new();
+
+    let extension = document
+        .productFootprint
+        .extensions
+        .first()
+        .ok_or(CalculationError::MissingExtension)?;
+
+    for tce in &extension.data.tces {
+        check_tce_values(tce)?;
+
+        if let (Some(toc_id), Some(distance)) = (&tce.tocId, &tce.distance) {
+            let emission_factor = emission_factor_toc(&document.tocData, &tce.tceId, toc_id)?;
+            let emissions = tce.mass * emission_factor * distance.actual;
+
+            for signed_sensor_data in document.signedSensorData.iter().flatten() {
+                if signed_sensor_data.tceId != tce.tceId {
+                    continue;
+                }
+                let commitment = sensor_data_commitment(
+                    &signed_sensor_data.sensorData,
+                    &signed_sensor_data.salt,
+                );
+                if commitment != signed_sensor_data.commitment {
+                    return Err(CalculationError::CommitmentMismatch {
+                        tce_id: tce.tceId.clone(),
+                    });
+                }
+                sensor_data.push(signed_sensor_data);
+            }
+
+            pcf += emissions;
+        }
+
+        if let Some(hoc_id) = &tce.hocId {
+            let emission_factor = emission_factor_hoc(&document.hocData, &tce.tceId, hoc_id)?;
+            pcf += tce.mass * emission_factor;
+        }
+    }
+
+    if !pcf.is_finite() {
+        return Err(CalculationError::NonFinitePcf);
+    }
+    Ok(PcfCalculation { pcf, sensor_data })
+}
+
+/// Native preview of the PCF, where upstream proofs count with the PCF they
+/// claim instead of the one from their verified journal.
+pub fn preview_pcf(document: &ProofingDocument) -> Result<f64, CalculationError> {
+    let upstream_pcf = document
+        .proof
+        .iter()
+        .fold(0.0, |pcf, proof| proof.pcf + pcf);
+    calculate_pcf(document, upstream_pcf).map(|calculation| calculation.pcf)
+}
+
+fn emission_factor_toc(
+    toc_data: &[TocData],
+    tce_id: &str,
+    toc_id: &str,
+) -> Result<f64, CalculationError> {
+    let toc = toc_data
+        .iter()
+        .find(|toc| toc.tocId == toc_id)
+        .ok_or_else(|| CalculationError::UnknownToc {
+            tce_id: tce_id.to_string(),
+            toc_id: toc_id.to_string(),
+        })?;
+    parse_intensity(&toc.co2eIntensityWTW).ok_or_else(|| CalculationError::InvalidIntensity {
+        tce_id: tce_id.to_string(),
+        id: toc.tocId.clone(),
+        intensity: toc.co2eIntensityWTW.clone(),
+    })
+}
+
+fn emission_factor_hoc(
+    hoc_data: &[HocData],
+    tce_id: &str,
+    hoc_id: &str,
+) -> Result<f64, CalculationError> {
+    let hoc = hoc_data
+        .iter()
+        .find(|hoc| hoc.hocId == hoc_id)
+        .ok_or_else(|| CalculationError::UnknownHoc {
+            tce_id: tce_id.to_string(),
+            hoc_id: hoc_id.to_string(),
+        })?;
+    parse_intensity(&hoc.co2eIntensityWTW).ok_or_else(|| CalculationError::InvalidIntensity {
+        tce_id: tce_id.to_string(),
+        id: hoc.hocId.clone(),
+        intensity: hoc.co2eIntensityWTW.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100 km road leg at 80 g/tkm followed by a hub at 5 g/t, both for 2 t.
+    fn document() -> ProofingDocument {
+        serde_json::from_value(serde_json::json!({
+            "productFootprint": {
+                "id": "pf-1",
+                "created": "2025-01-01T00:00:00Z",
+                "companyName": "Carrier",
+                "companyIds": [],
+                "productDescription": "Transport",
+                "productIds": [],
+                "productCategoryCpc": 6511,
+                "productNameCompany": "Transport",
+                "pcf": null,
+                "extensions": [{
+                    "dataSchema": "https://api.ileap.sine.dev/shipment-footprint.json",
+                    "data": {
+                        "mass": 2.0,
+                        "shipmentId": "shipment-1",
+                        "tces": [
+                            {
+                                "tceId": "tce-1",
+                                "tocId": "toc-1",
+                                "hocId": null,
+                                "shipmentId": "shipment-1",
+                                "mass": 2.0,
+                                "co2eWTW": null,
+                                "co2eTTW": null,
+                                "transportActivity": null,
+                                "distance": { "actual": 100.0, "gcd": null, "sfd": null }
+                            },
+                            {
+                                "tceId": "tce-2",
+                                "prevTceIds": ["tce-1"],
+                                "tocId": null,
+                                "hocId": "hoc-1",
+                                "shipmentId": "shipment-1",
+                                "mass": 2.0,
+                                "co2eWTW": null,
+                                "co2eTTW": null,
+                                "transportActivity": null,
+                                "distance": null
+                            }
+                        ]
+                    }
+                }]
+            },
+            "tocData": [{
+                "tocId": "toc-1",
+                "certifications": [],
+                "description": "Diesel truck",
+                "mode": "road",
+                "loadFactor": "0.8",
+                "emptyDistanceFactor": "0.1",
+                "temperatureControl": "ambient",
+                "truckLoadingSequence": "FTL",
+                "airShippingOption": null,
+                "flightLength": null,
+                "energyCarriers": [],
+                "co2eIntensityWTW": "80 gCO2e/tkm",
+                "co2eIntensityTTW": "60 gCO2e/tkm",
+                "transportActivityUnit": "tkm"
+            }],
+            "hocData": [{
+                "hocId": "hoc-1",
+                "passhubType": "Transshipment",
+                "energyCarriers": [],
+                "co2eIntensityWTW": "5 gCO2e/t",
+                "co2eIntensityTTW": "0 gCO2e/t",
+                "hubActivityUnit": "tonnes"
+            }],
+            "signedSensorData": null,
+            "proof": []
+        }))
+        .expect("valid document")
+    }
+
+    fn tces(document: &mut ProofingDocument) -> &mut Vec<TCE> {
+        &mut document.productFootprint.extensions[0].data.tces
+    }
+
+    #[test]
+    fn parses_intensities_within_bounds() {
+        assert_eq!(parse_intensity("85 gCO2e/tkm"), Some(85.0));
+        assert_eq!(parse_intensity("0.5"), Some(0.5));
+        assert_eq!(parse_intensity("1e6 gCO2e/t"), Some(MAX_INTENSITY));
+        for invalid in [
+            "",
+            " 85",
+            "85gCO2e/tkm",
+            "-1 gCO2e/tkm",
+            "1e7",
+            "NaN",
+            "inf",
+        ] {
+            assert_eq!(parse_intensity(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn checks_mass_and_distance_bounds() {
+        let mut document = document();
+        let tce = &mut tces(&mut document)[0];
+        assert_eq!(check_tce_values(tce), Ok(()));
+
+        tce.mass = f64::NAN;
+        let err = check_tce_values(tce).unwrap_err();
+        assert_eq!(err.code(), "invalidValue");
+        assert_eq!(err.tce_id(), Some("tce-1"));
+
+        tce.mass = MAX_MASS;
+        tce.distance.as_mut().unwrap().actual = MAX_DISTANCE * 2.0;
+        assert!(matches!(
+            check_tce_values(tce),
+            Err(CalculationError::InvalidValue {
+                field: "distance",
+                ..
+            })
+        ));
+
+        tce.distance.as_mut().unwrap().actual = -0.1;
+        assert!(check_tce_values(tce).is_err());
+    }
+
+    #[test]
+    fn sums_transport_legs_hubs_and_upstream_pcf() {
+        let document = document();
+        let calculation = calculate_pcf(&document, 3.0).unwrap();
+        assert_eq!(calculation.pcf, 3.0 + 2.0 * 80.0 * 100.0 + 2.0 * 5.0);
+        assert!(calculation.sensor_data.is_empty());
+    }
+
+    #[test]
+    fn skips_transport_legs_without_distance() {
+        let mut document = document();
+        tces(&mut document)[0].distance = None;
+        assert_eq!(calculate_pcf(&document, 0.0).unwrap().pcf, 10.0);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        let mut document = document();
+        tces(&mut document)[0].tocId = Some("toc-2".to_string());
+        assert_eq!(
+            calculate_pcf(&document, 0.0).unwrap_err(),
+            CalculationError::UnknownToc {
+                tce_id: "tce-1".to_string(),
+                toc_id: "toc-2".to_string()
+            }
+        );
+
+        let mut document = self::document();
+        document.hocData[0].co2eIntensityWTW = "-5 gCO2e/t".to_string();
+        assert_eq!(
+            calculate_pcf(&document, 0.0).unwrap_err().code(),
+            "invalidIntensity"
+        );
+
+        let mut document = self::document();
+        document.productFootprint.extensions.clear();
+        assert_eq!(
+            calculate_pcf(&document, 0.0).unwrap_err(),
+            CalculationError::MissingExtension
+        );
+
+        let document = self::document();
+        for upstream_pcf in [-1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                calculate_pcf(&document, upstream_pcf).unwrap_err().code(),
+                "invalidUpstreamPcf"
+            );
+        }
+    }
+
+    #[test]
+    fn checks_sensor_data_commitments() {
+        let mut document = document();
+        let sensor_data = SensorData {
+            distance: tces(&mut document)[0].distance.clone().unwrap(),
+        };
+        let mut signed = TceSensorData {
+            tceId: "tce-1".to_string(),
+            sensorkey: String::new(),
+            signedSensorData: String::new(),
+            commitment: sensor_data_commitment(&sensor_data, "salt"),
+            sensorData: sensor_data,
+            salt: "salt".to_string(),
+        };
+        document.signedSensorData = Some(vec![signed.clone()]);
+        let calculation = calculate_pcf(&document, 0.0).unwrap();
+        assert_eq!(calculation.sensor_data.len(), 1);
+
+        signed.salt = "pepper".to_string();
+        document.signedSensorData = Some(vec![signed]);
+        assert_eq!(
+            calculate_pcf(&document, 0.0).unwrap_err(),
+            CalculationError::CommitmentMismatch {
+                tce_id: "tce-1".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,227 @@
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::merkle::{MerkleProof, MerkleTree};
+use crate::product_footprint::TCE;
+use crate::proofing_document::ProofingDocument;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InclusionProof {
+    /// `tceId`, `tocId` or `hocId` of the proven entry.
+    pub id: String,
+    pub proof: MerkleProof,
+}
+
+/// Inclusion proofs for every TCE, TOC and HOC entry of a proven document,
+/// matching the Merkle roots committed to the journal.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DisclosureProofs {
+    pub tces: Vec<InclusionProof>,
+    pub tocs: Vec<InclusionProof>,
+    pub hocs: Vec<InclusionProof>,
+}
+
+fn inclusion_proofs<T: Serialize>(items: &[T], id: impl Fn(&T) -> &str) -> Vec<InclusionProof> {
+    let tree = MerkleTree::from_items(items);
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            tree.proof(index).map(|proof| InclusionProof {
+                id: id(item).to_string(),
+                proof,
+            })
+        })
+        .collect()
+}
+
+impl DisclosureProofs {
+    pub fn for_document(document: &ProofingDocument) -> Self {
+        DisclosureProofs {
+            tces: inclusion_proofs(document_tces(document), |t| t.tceId.as_str()),
+            tocs: inclusion_proofs(&document.tocData, |t| t.tocId.as_str()),
+            hocs: inclusion_proofs(&document.hocData, |h| h.hocId.as_str()),
+        }
+    }
+}
+
+/// TCEs of the iLEAP extension the guest calculates with.
+pub fn document_tces(document: &ProofingDocument) -> &[TCE] {
+    document
+        .productFootprint
+        .extensions
+        .first()
+        .map(|ext| ext.data.tces.as_slice())
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "zkvm"))]
+pub use verify::*;
+
+#[cfg(not(target_os = "zkvm"))]
+mod verify {
+    use std::fmt;
+
+    use base64::{engine::general_purpose, Engine as _};
+    use risc0_zkvm::{sha::Digest, Receipt};
+    use serde::Serialize;
+
+    use crate::hoc_toc_data::{HocData, TocData};
+    use crate::journal::ProofJournal;
+    use crate::merkle::{leaf_hash, MerkleProof};
+    use crate::product_footprint::{ProductProof, TCE};
+
+    #[derive(Debug)]
+    pub enum DisclosureError {
+        InvalidReceipt(String),
+        InvalidImageId(String),
+        UntrustedImageId(Digest),
+        ReceiptVerification(String),
+        InvalidJournal(String),
+        NotIncluded,
+    }
+
+    impl fmt::Display for DisclosureError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                DisclosureError::InvalidReceipt(e) => write!(f, "invalid receipt: {}", e),
+                DisclosureError::InvalidImageId(e) => write!(f, "invalid image ID: {}", e),
+                DisclosureError::UntrustedImageId(id) => {
+                    write!(f, "image ID {} is not a trusted proofing guest", id)
+                }
+                DisclosureError::ReceiptVerification(e) => {
+                    write!(f, "receipt verification failed: {}", e)
+                }
+                DisclosureError::InvalidJournal(e) => write!(f, "invalid journal: {}", e),
+                DisclosureError::NotIncluded => {
+                    write!(f, "disclosed entry is not part of the proof")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for DisclosureError {}
+
+    /// Verifies the inline receipt of `product_proof` against its image ID and
+    /// returns the decoded journal.
+    ///
+    /// The image ID is declared by the proof itself, so it has to be one of
+    /// `trusted_image_ids`, the proofing guest versions the verifier accepts;
+    /// otherwise any guest could produce a matching receipt.
+    pub fn verified_journal(
+        product_proof: &ProductProof,
+        trusted_image_ids: &[Digest],
+    ) -> Result<ProofJournal, DisclosureError> {
+        if let (true, Some(uri)) = (
+            product_proof.proofReceipt.is_empty(),
+            &product_proof.receiptUri,
+        ) {
+            return Err(DisclosureError::InvalidReceipt(format!(
+                "receipt is stored externally at {}",
+                uri
+            )));
+        }
+
+        let image_id_bytes: [u8; 32] = hex::decode(&product_proof.imageId)
+            .map_err(|e| DisclosureError::InvalidImageId(e.to_string()))?
+            .try_into()
+            .map_err(|_| DisclosureError::InvalidImageId("not 32 bytes long".to_string()))?;
+        let image_id = Digest::from(image_id_bytes);
+        if !trusted_image_ids.contains(&image_id) {
+            return Err(DisclosureError::UntrustedImageId(image_id));
+        }
+
+        let receipt_bytes = general_purpose::STANDARD
+            .decode(&product_proof.proofReceipt)
+            .map_err(|e| DisclosureError::InvalidReceipt(e.to_string()))?;
+        let receipt: Receipt = bincode::deserialize(&receipt_bytes)
+            .map_err(|e| DisclosureError::InvalidReceipt(e.to_string()))?;
+
+        receipt
+            .verify(image_id)
+            .map_err(|e| DisclosureError::ReceiptVerification(e.to_string()))?;
+
+        receipt
+            .journal
+            .decode()
+            .map_err(|e| DisclosureError::InvalidJournal(e.to_string()))
+    }
+
+    fn verify_disclosed<T: Serialize>(
+        product_proof: &ProductProof,
+        trusted_image_ids: &[Digest],
+        item: &T,
+        inclusion: &MerkleProof,
+        root: impl Fn(&ProofJournal) -> Digest,
+    ) -> Result<(), DisclosureError> {
+        let journal = verified_journal(product_proof, trusted_image_ids)?;
+        if inclusion.verify(leaf_hash(item), root(&journal)) {
+            Ok(())
+        } else {
+            Err(DisclosureError::NotIncluded)
+        }
+    }
+
+    /// Checks that a disclosed TCE was part of the document proven by `product_proof`.
+    pub fn verify_disclosed_tce(
+        product_proof: &ProductProof,
+        trusted_image_ids: &[Digest],
+        tce: &TCE,
+        inclusion: &MerkleProof,
+    ) -> Result<(), DisclosureError> {
+        verify_disclosed(product_proof, trusted_image_ids, tce, inclusion, |j| {
+            j.tce_root
+        })
+    }
+
+    pub fn verify_disclosed_toc(
+        product_proof: &ProductProof,
+        trusted_image_ids: &[Digest],
+        toc: &TocData,
+        inclusion: &MerkleProof,
+    ) -> Result<(), DisclosureError> {
+        verify_disclosed(product_proof, trusted_image_ids, toc, inclusion, |j| {
+            j.toc_root
+        })
+    }
+
+    pub fn verify_disclosed_hoc(
+        product_proof: &ProductProof,
+        trusted_image_ids: &[Digest],
+        hoc: &HocData,
+        inclusion: &MerkleProof,
+    ) -> Result<(), DisclosureError> {
+        verify_disclosed(product_proof, trusted_image_ids, hoc, inclusion, |j| {
+            j.hoc_root
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use risc0_zkvm::sha::Digest;
+
+    use super::*;
+    use crate::product_footprint::ProductProof;
+
+    #[test]
+    fn rejects_untrusted_image_ids() {
+        let product_proof: ProductProof = serde_json::from_value(serde_json::json!({
+            "productFootprintId": "pf-1",
+            "proofReceipt": "",
+            "pcf": 1.0,
+            "proofReference": "ref",
+            "imageId": hex::encode([7u8; 32])
+        }))
+        .unwrap();
+
+        let result = verified_journal(&product_proof, &[Digest::ZERO]);
+        assert!(matches!(
+            result,
+            Err(DisclosureError::UntrustedImageId(id)) if id == Digest::from([7u8; 32])
+        ));
+    }
+}
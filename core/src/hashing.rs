@@ -0,0 +1,13 @@
+use risc0_zkvm::sha::Digest;
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+/// SHA-256 over the canonical JSON encoding of `value`.
+///
+/// The encoding is the `serde_json` serialization of the typed value, so field
+/// order follows the struct definitions and is identical in guest and host.
+pub fn canonical_hash<T: Serialize + ?Sized>(value: &T) -> Digest {
+    let bytes = serde_json::to_vec(value).expect("Failed to serialize value for hashing");
+    let hash: [u8; 32] = Sha256::digest(&bytes).into();
+    Digest::from(hash)
+}
@@ -3,6 +3,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum CertificationEnum {
     #[serde(rename = "ISO14083:2023")]
     Iso14083_2023,
@@ -15,6 +16,7 @@ pub enum CertificationEnum {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum TransportMode {
     #[serde(rename = "road")]
     Road,
@@ -27,6 +29,7 @@ pub enum TransportMode {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EnergyCarriers {
     pub energyCarrier: String,
     pub relativeShare: String,
@@ -35,6 +38,7 @@ pub struct EnergyCarriers {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TocData {
     pub tocId: String,
     pub certifications: Vec<CertificationEnum>,
@@ -53,6 +57,7 @@ pub struct TocData {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HocData {
     pub hocId: String,
     pub passhubType: String,
@@ -0,0 +1,115 @@
+use risc0_zkvm::sha::Digest;
+use serde::{Deserialize, Serialize};
+
+use crate::calculation::CalculationError;
+use crate::hashing::canonical_hash;
+use crate::proofing_document::ProofingDocument;
+
+/// Public output committed by the guest.
+///
+/// `pcf` and `sig_containers` come first so journals stay readable by code
+/// that only decodes the leading PCF value.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProofJournal {
+    pub pcf: f64,
+    /// Bincode serialized `Vec<SignatureContainer>`.
+    pub sig_containers: Vec<u8>,
+    pub product_footprint_id: String,
+    pub shipment_id: String,
+    /// Hash of the whole `ProofingDocument` the guest received (with `proof` emptied).
+    pub document_hash: Digest,
+    pub toc_data_hash: Digest,
+    pub hoc_data_hash: Digest,
+    /// Merkle roots over the TCEs, TOC and HOC entries for selective disclosure.
+    pub tce_root: Digest,
+    pub toc_root: Digest,
+    pub hoc_root: Digest,
+    /// Image IDs the guest accepted for composed upstream receipts.
+    pub allowed_image_ids: Vec<Digest>,
+    /// Image ID whose receipts were composed as aggregations; verifiers must
+    /// check it like `AggregationJournal::aggregation_image_id`.
+    pub aggregation_image_id: Digest,
+}
+
+impl ProofJournal {
+    /// Checks that this journal was produced for `document`.
+    ///
+    /// Upstream proofs are passed to the guest as assumptions, not as part of the
+    /// document, so they are ignored for the comparison.
+    pub fn binds(&self, document: &ProofingDocument) -> bool {
+        let mut document = document.clone();
+        document.proof = Vec::new();
+
+        let shipment_id = document
+            .productFootprint
+            .extensions
+            .first()
+            .map(|ext| ext.data.shipmentId.as_str());
+
+        self.product_footprint_id == document.productFootprint.id
+            && Some(self.shipment_id.as_str()) == shipment_id
+            && self.toc_data_hash == canonical_hash(&document.tocData)
+            && self.hoc_data_hash == canonical_hash(&document.hocData)
+            && self.document_hash == canonical_hash(&document)
+    }
+}
+
+/// Public output of the aggregation guest.
+///
+/// Shares its leading fields with `ProofJournal`, so aggregated receipts can be
+/// composed like any other upstream proof.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AggregationJournal {
+    /// Sum of the PCFs of all aggregated proofs.
+    pub pcf: f64,
+    /// Bincode serialized `Vec<SignatureContainer>` of all aggregated proofs.
+    pub sig_containers: Vec<u8>,
+    /// Number of proofing guest receipts covered by this aggregation.
+    pub leaf_count: u64,
+    pub allowed_image_ids: Vec<Digest>,
+    /// Image ID of the aggregation guest itself; verifiers must check it.
+    pub aggregation_image_id: Digest,
+}
+
+/// Exit code of a proofing guest that committed a [`Rejection`]. Receipts with
+/// a non-zero exit code neither verify nor compose as successful proofs.
+pub const REJECTED_EXIT_CODE: u8 = 1;
+
+/// Committed instead of a `ProofJournal` when the guest rejects a document, so
+/// the receipt proves why the document is invalid.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Rejection {
+    /// `CalculationError::code`, e.g. `unknownToc`.
+    pub code: String,
+    /// The offending TCE, unless the whole document is affected.
+    pub tce_id: Option<String>,
+    pub message: String,
+}
+
+/// `Rejection` as committed by guest 0.7.x, before the TCE was reported.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LegacyRejection {
+    pub code: String,
+    pub message: String,
+}
+
+impl From<LegacyRejection> for Rejection {
+    fn from(rejection: LegacyRejection) -> Self {
+        Rejection {
+            code: rejection.code,
+            tce_id: None,
+            message: rejection.message,
+        }
+    }
+}
+
+impl From<&CalculationError> for Rejection {
+    fn from(error: &CalculationError) -> Self {
+        Rejection {
+            code: error.code().to_string(),
+            tce_id: error.tce_id().map(str::to_string),
+            message: error.to_string(),
+        }
+    }
+}
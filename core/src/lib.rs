@@ -1,5 +1,17 @@
+extern crate alloc;
+
+pub mod aggregation_request;
+pub mod calculation;
+pub mod disclosure;
+pub mod hashing;
 pub mod hoc_toc_data;
+pub mod journal;
+pub mod merkle;
 pub mod product_footprint;
 pub mod proofing_document;
 pub mod proof_container;
-pub mod sig_container;
\ No newline at end of file
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod sig_container;
+#[cfg(not(target_os = "zkvm"))]
+pub mod validation;
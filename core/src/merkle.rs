@@ -0,0 +1,182 @@
+use risc0_zkvm::sha::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Leaf hash of an item: SHA-256 over a domain prefix and its canonical JSON.
+pub fn leaf_hash<T: Serialize + ?Sized>(item: &T) -> Digest {
+    let bytes = serde_json::to_vec(item).expect("Failed to serialize Merkle leaf");
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(&bytes);
+    let hash: [u8; 32] = hasher.finalize().into();
+    Digest::from(hash)
+}
+
+fn node_hash(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    let hash: [u8; 32] = hasher.finalize().into();
+    Digest::from(hash)
+}
+
+/// Binary Merkle tree. An unpaired node is promoted to the next level unchanged.
+pub struct MerkleTree {
+    levels: Vec<Vec<Digest>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<Digest>) -> Self {
+        let mut levels = vec![leaves];
+
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        MerkleTree { levels }
+    }
+
+    pub fn from_items<T: Serialize>(items: &[T]) -> Self {
+        Self::new(items.iter().map(leaf_hash).collect())
+    }
+
+    /// Root of the tree; all zeros for an empty tree.
+    pub fn root(&self) -> Digest {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_else(|| Digest::from([0u8; 32]))
+    }
+
+    /// Inclusion proof for the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+
+        let mut steps = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                steps.push(MerkleStep {
+                    hash: *hash,
+                    left: sibling < index,
+                });
+            }
+            index /= 2;
+        }
+
+        Some(MerkleProof { steps })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MerkleStep {
+    #[cfg_attr(feature = "schema", schemars(with = "[u32; 8]"))]
+    pub hash: Digest,
+    /// Whether the sibling is the left input of the parent node.
+    pub left: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MerkleProof {
+    pub steps: Vec<MerkleStep>,
+}
+
+impl MerkleProof {
+    pub fn verify(&self, leaf: Digest, root: Digest) -> bool {
+        let computed = self.steps.iter().fold(leaf, |acc, step| {
+            if step.left {
+                node_hash(&step.hash, &acc)
+            } else {
+                node_hash(&acc, &step.hash)
+            }
+        });
+        computed == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<Digest> {
+        (0..count)
+            .map(|i| leaf_hash(&format!("leaf-{}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn proves_every_leaf() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(leaves.clone());
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).expect("index in range");
+                assert!(proof.verify(*leaf, tree.root()), "{} of {}", index, count);
+            }
+            assert!(tree.proof(count).is_none());
+        }
+    }
+
+    #[test]
+    fn promotes_the_unpaired_node() {
+        let leaves = leaves(3);
+        let tree = MerkleTree::new(leaves.clone());
+        assert_eq!(
+            tree.root(),
+            node_hash(&node_hash(&leaves[0], &leaves[1]), &leaves[2])
+        );
+
+        // The promoted leaf only needs the sibling of its parent
+        let proof = tree.proof(2).unwrap();
+        assert_eq!(
+            proof.steps,
+            vec![MerkleStep {
+                hash: node_hash(&leaves[0], &leaves[1]),
+                left: true,
+            }]
+        );
+        assert!(proof.verify(leaves[2], tree.root()));
+    }
+
+    #[test]
+    fn rejects_excluded_leaves() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(leaves.clone());
+        let proof = tree.proof(1).unwrap();
+
+        assert!(!proof.verify(leaves[0], tree.root()));
+        assert!(!proof.verify(leaf_hash("not in the tree"), tree.root()));
+        assert!(!proof.verify(leaves[1], MerkleTree::new(leaves[..4].to_vec()).root()));
+
+        // Leaves and inner nodes are hashed with different prefixes
+        let pair = [leaves[0], leaves[1]];
+        assert_ne!(leaf_hash(&pair), node_hash(&leaves[0], &leaves[1]));
+    }
+
+    #[test]
+    fn empty_tree_has_zero_root() {
+        let tree = MerkleTree::new(Vec::new());
+        assert_eq!(tree.root(), Digest::from([0u8; 32]));
+        assert!(tree.proof(0).is_none());
+    }
+}
@@ -1,15 +1,22 @@
 #![allow(non_snake_case)]
 
-use serde::{Deserialize, Serialize};
+use core::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::disclosure::DisclosureProofs;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Distance {
+    #[cfg_attr(feature = "schema", schemars(range(min = 0)))]
     pub actual: f64,
     pub gcd: Option<f64>,
     pub sfd: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TCE {
     pub tceId: String,
     #[serde(default)]
@@ -17,6 +24,7 @@ pub struct TCE {
     pub hocId: Option<String>,
     pub tocId: Option<String>,
     pub shipmentId: String,
+    #[cfg_attr(feature = "schema", schemars(range(min = 0)))]
     pub mass: f64,
     pub co2eWTW: Option<f64>,
     pub co2eTTW: Option<f64>,
@@ -25,15 +33,33 @@ pub struct TCE {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProductProof {
     pub productFootprintId: String,
+    /// Base64 encoded, bincode serialized receipt; empty if `receiptUri` is set.
+    #[serde(default)]
     pub proofReceipt: String,
     pub pcf: f64,
     pub proofReference: String,
     pub imageId: String,//[u32; 8],
+    /// Version of the guest program that produced `proofReceipt`.
+    #[serde(default)]
+    pub guestVersion: Option<String>,
+    /// `composite`, `succinct` or `groth16`.
+    #[serde(default)]
+    pub receiptKind: Option<String>,
+    /// Location of the receipt in a receipt store, instead of inlining it.
+    #[serde(default)]
+    pub receiptUri: Option<String>,
+    /// Hex SHA-256 of the bincode serialized receipt.
+    #[serde(default)]
+    pub receiptDigest: Option<String>,
+    #[serde(default)]
+    pub disclosureProofs: Option<DisclosureProofs>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Extension {
     #[serde(default = "default_spec_version")]
     pub specVersion: String,
@@ -42,7 +68,9 @@ pub struct Extension {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ExtensionData {
+    #[cfg_attr(feature = "schema", schemars(range(min = 0)))]
     pub mass: f64,
     pub shipmentId: String,
     #[serde(default)]
@@ -50,6 +78,7 @@ pub struct ExtensionData {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProofExtension {
     #[serde(default = "default_spec_version")]
     pub specVersion: String,
@@ -57,8 +86,12 @@ pub struct ProofExtension {
     pub data: ProofExtensionData,
 }
 
+/// Data of the PACT extension appended by the proving service. Each entry of
+/// `pcfProofs` is a RISC Zero receipt attesting the PCF of the footprint.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProofExtensionData {
+    #[cfg_attr(feature = "schema", schemars(range(min = 0)))]
     pub mass: f64,
     pub shipmentId: String,
     pub pcfProofs: Vec<ProductProof>,
@@ -68,8 +101,68 @@ fn default_spec_version() -> String {
     "2.0.0".to_string()
 }
 
+/// Extensions of a completed footprint: the original iLEAP shipment data plus
+/// the proof extension appended by the proving service.
+///
+/// JSON keeps PACT's plain extension objects. Binary formats like postcard
+/// carry no field names to tell the two apart, so there the variant is tagged.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(untagged))]
+pub enum FootprintExtension {
+    Proof(ProofExtension),
+    Ileap(Extension),
+}
+
+impl Serialize for FootprintExtension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match (self, serializer.is_human_readable()) {
+            (FootprintExtension::Proof(extension), true) => extension.serialize(serializer),
+            (FootprintExtension::Ileap(extension), true) => extension.serialize(serializer),
+            (FootprintExtension::Proof(extension), false) => {
+                serializer.serialize_newtype_variant("FootprintExtension", 0, "Proof", extension)
+            }
+            (FootprintExtension::Ileap(extension), false) => {
+                serializer.serialize_newtype_variant("FootprintExtension", 1, "Ileap", extension)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FootprintExtension {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Untagged {
+            Proof(ProofExtension),
+            Ileap(Extension),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename = "FootprintExtension")]
+        enum Tagged {
+            Proof(ProofExtension),
+            Ileap(Extension),
+        }
+
+        if deserializer.is_human_readable() {
+            Ok(match Untagged::deserialize(deserializer)? {
+                Untagged::Proof(extension) => FootprintExtension::Proof(extension),
+                Untagged::Ileap(extension) => FootprintExtension::Ileap(extension),
+            })
+        } else {
+            Ok(match Tagged::deserialize(deserializer)? {
+                Tagged::Proof(extension) => FootprintExtension::Proof(extension),
+                Tagged::Ileap(extension) => FootprintExtension::Ileap(extension),
+            })
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ProductFootprint {
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(bound(deserialize = "E: Deserialize<'de>"))]
+pub struct ProductFootprint<E = Extension> {
     pub id: String,
     #[serde(default = "default_spec_version")]
     pub specVersion: String,
@@ -88,7 +181,83 @@ pub struct ProductFootprint {
     #[serde(default = "default_comment")]
     pub comment: String,
     #[serde(default)]
-    pub extensions: Vec<Extension>,
+    pub extensions: Vec<E>,
+}
+
+/// The footprint is already at the highest `version`, so its completed
+/// revision cannot be numbered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionOverflow {
+    pub id: String,
+    pub version: i32,
+}
+
+impl fmt::Display for VersionOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "version {} of footprint {} cannot be incremented",
+            self.version, self.id
+        )
+    }
+}
+
+impl std::error::Error for VersionOverflow {}
+
+impl ProductFootprint {
+    /// Completes the footprint with the proven PCF: fills in `pcf`, bumps
+    /// `version` and appends a `ProofExtension` carrying the `ProductProof`.
+    pub fn into_proven(
+        self,
+        proof: ProductProof,
+        data_schema: &str,
+    ) -> Result<ProductFootprint<FootprintExtension>, VersionOverflow> {
+        let Some(version) = self.version.checked_add(1) else {
+            return Err(VersionOverflow {
+                id: self.id,
+                version: self.version,
+            });
+        };
+        let pcf = proof.pcf;
+        let (mass, shipment_id) = self
+            .extensions
+            .first()
+            .map(|ext| (ext.data.mass, ext.data.shipmentId.clone()))
+            .unwrap_or_default();
+
+        let mut extensions: Vec<FootprintExtension> = self
+            .extensions
+            .into_iter()
+            .map(FootprintExtension::Ileap)
+            .collect();
+
+        extensions.push(FootprintExtension::Proof(ProofExtension {
+            specVersion: default_spec_version(),
+            dataSchema: data_schema.to_string(),
+            data: ProofExtensionData {
+                mass,
+                shipmentId: shipment_id,
+                pcfProofs: vec![proof],
+            },
+        }));
+
+        Ok(ProductFootprint {
+            id: self.id,
+            specVersion: self.specVersion,
+            version,
+            created: self.created,
+            status: self.status,
+            companyName: self.companyName,
+            companyIds: self.companyIds,
+            productDescription: self.productDescription,
+            productIds: self.productIds,
+            productCategoryCpc: self.productCategoryCpc,
+            productNameCompany: self.productNameCompany,
+            pcf: Some(pcf),
+            comment: self.comment,
+            extensions,
+        })
+    }
 }
 
 fn default_status() -> String {
@@ -97,4 +266,65 @@ fn default_status() -> String {
 
 fn default_comment() -> String {
     "".to_string()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn footprint(version: i32) -> ProductFootprint {
+        serde_json::from_value(serde_json::json!({
+            "id": "pf-1",
+            "version": version,
+            "created": "2025-01-01T00:00:00Z",
+            "companyName": "Carrier",
+            "companyIds": [],
+            "productDescription": "Transport",
+            "productIds": [],
+            "productCategoryCpc": 6511,
+            "productNameCompany": "Transport",
+            "pcf": null,
+            "extensions": [{
+                "dataSchema": "https://api.ileap.sine.dev/shipment-footprint.json",
+                "data": { "mass": 1000.0, "shipmentId": "shipment-1", "tces": [] }
+            }]
+        }))
+        .expect("valid footprint")
+    }
+
+    fn proof() -> ProductProof {
+        serde_json::from_value(serde_json::json!({
+            "productFootprintId": "pf-1",
+            "proofReceipt": "",
+            "pcf": 12.5,
+            "proofReference": "ref",
+            "imageId": "00"
+        }))
+        .expect("valid proof")
+    }
+
+    #[test]
+    fn into_proven_completes_the_footprint() {
+        let proven = footprint(3).into_proven(proof(), "schema").unwrap();
+        assert_eq!(proven.version, 4);
+        assert_eq!(proven.pcf, Some(12.5));
+        assert!(matches!(
+            proven.extensions.as_slice(),
+            [FootprintExtension::Ileap(_), FootprintExtension::Proof(p)]
+                if p.data.shipmentId == "shipment-1" && p.data.pcfProofs.len() == 1
+        ));
+    }
+
+    #[test]
+    fn into_proven_rejects_the_highest_version() {
+        let err = footprint(i32::MAX)
+            .into_proven(proof(), "schema")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            VersionOverflow {
+                id: "pf-1".to_string(),
+                version: i32::MAX
+            }
+        );
+    }
+}
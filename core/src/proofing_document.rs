@@ -7,6 +7,7 @@ use super::hoc_toc_data::{HocData, TocData};
 use super::product_footprint::{ProductFootprint, Distance};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TceSensorData {
     pub tceId: String,
     pub sensorkey: String,
@@ -17,6 +18,7 @@ pub struct TceSensorData {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProofingDocument {
     pub productFootprint: ProductFootprint,
     pub tocData: Vec<TocData>,
@@ -26,6 +28,7 @@ pub struct ProofingDocument {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SensorData {
     pub distance: Distance,
 }
\ No newline at end of file
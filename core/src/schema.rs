@@ -0,0 +1,57 @@
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use crate::aggregation_request::AggregationRequest;
+use crate::product_footprint::ProofExtensionData;
+use crate::proofing_document::ProofingDocument;
+
+/// JSON Schema of the `ProofingDocument` accepted by the proving service.
+pub fn proofing_document_schema() -> RootSchema {
+    schema_for!(ProofingDocument)
+}
+
+/// JSON Schema of the `AggregationRequest` accepted in aggregation mode.
+pub fn aggregation_request_schema() -> RootSchema {
+    schema_for!(AggregationRequest)
+}
+
+/// JSON Schema of the proof extension data, the `dataSchema` of completed footprints.
+pub fn proof_extension_schema() -> RootSchema {
+    schema_for!(ProofExtensionData)
+}
+
+/// Published schemas by their file name in `schemas/`.
+pub fn published_schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("proofing-document.schema.json", proofing_document_schema()),
+        (
+            "aggregation-request.schema.json",
+            aggregation_request_schema(),
+        ),
+        ("proof-extension.schema.json", proof_extension_schema()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::published_schemas;
+
+    #[test]
+    fn published_schemas_are_up_to_date() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../schemas");
+        for (file_name, schema) in published_schemas() {
+            let published: serde_json::Value = std::fs::read_to_string(dir.join(file_name))
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_else(|| panic!("{} is missing or invalid", file_name));
+            assert_eq!(
+                published,
+                serde_json::to_value(&schema).unwrap(),
+                "{} is stale, run the generate_schema example",
+                file_name
+            );
+        }
+    }
+}
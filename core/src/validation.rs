@@ -0,0 +1,210 @@
+use std::fmt;
+
+use base64::{engine::general_purpose, Engine as _};
+
+pub use crate::calculation::parse_intensity;
+use crate::calculation::{
+    check_tce_values, sensor_data_commitment, CalculationError, MAX_INTENSITY,
+};
+use crate::hoc_toc_data::{HocData, TocData};
+use crate::product_footprint::{ProductProof, TCE};
+use crate::proofing_document::{ProofingDocument, TceSensorData};
+
+/// A problem that would make the guest panic or silently drop emissions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// JSON pointer to the offending value.
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationIssue {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}': {}", self.path, self.message)
+    }
+}
+
+/// Checks a document against the expectations of the guest program.
+///
+/// Runs on the host before proving, so documents the guest would reject are
+/// reported in milliseconds and with every issue at once.
+pub fn validate(document: &ProofingDocument) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    let Some(extension) = document.productFootprint.extensions.first() else {
+        issues.push(ValidationIssue::new(
+            "/productFootprint/extensions",
+            "at least one iLEAP extension with TCEs is required",
+        ));
+        return issues;
+    };
+
+    for (index, toc) in document.tocData.iter().enumerate() {
+        if parse_intensity(&toc.co2eIntensityWTW).is_none() {
+            issues.push(ValidationIssue::new(
+                format!("/tocData/{}/co2eIntensityWTW", index),
+                format!(
+                    "'{}' does not start with a number between 0 and {}",
+                    toc.co2eIntensityWTW, MAX_INTENSITY
+                ),
+            ));
+        }
+    }
+    for (index, hoc) in document.hocData.iter().enumerate() {
+        if parse_intensity(&hoc.co2eIntensityWTW).is_none() {
+            issues.push(ValidationIssue::new(
+                format!("/hocData/{}/co2eIntensityWTW", index),
+                format!(
+                    "'{}' does not start with a number between 0 and {}",
+                    hoc.co2eIntensityWTW, MAX_INTENSITY
+                ),
+            ));
+        }
+    }
+
+    for (index, tce) in extension.data.tces.iter().enumerate() {
+        let path = format!("/productFootprint/extensions/0/data/tces/{}", index);
+        validate_tce(
+            tce,
+            &path,
+            &document.tocData,
+            &document.hocData,
+            &mut issues,
+        );
+    }
+    // The guest only calculates the first extension, but references must hold in all
+    let extensions = &document.productFootprint.extensions;
+    for (ext_index, other) in extensions.iter().enumerate().skip(1) {
+        for (index, tce) in other.data.tces.iter().enumerate() {
+            let path = format!(
+                "/productFootprint/extensions/{}/data/tces/{}",
+                ext_index, index
+            );
+            validate_references(
+                tce,
+                &path,
+                &document.tocData,
+                &document.hocData,
+                &mut issues,
+            );
+        }
+    }
+
+    if let Some(signed_sensor_data) = &document.signedSensorData {
+        for (index, sensor_data) in signed_sensor_data.iter().enumerate() {
+            let path = format!("/signedSensorData/{}", index);
+            validate_sensor_data(sensor_data, &path, &mut issues);
+        }
+    }
+
+    for (index, proof) in document.proof.iter().enumerate() {
+        validate_proof(proof, &format!("/proof/{}", index), &mut issues);
+    }
+
+    issues
+}
+
+fn validate_tce(
+    tce: &TCE,
+    path: &str,
+    toc_data: &[TocData],
+    hoc_data: &[HocData],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    // Same bounds the guest enforces
+    if let Err(e @ CalculationError::InvalidValue { field, .. }) = check_tce_values(tce) {
+        issues.push(ValidationIssue::new(
+            format!("{}/{}", path, field),
+            e.to_string(),
+        ));
+    }
+
+    validate_references(tce, path, toc_data, hoc_data, issues);
+
+    // The guest skips transport legs without a distance
+    if tce.tocId.is_some() && tce.distance.is_none() {
+        issues.push(ValidationIssue::new(
+            format!("{}/distance", path),
+            "a TCE with a TOC requires a distance",
+        ));
+    }
+}
+
+/// Checks that the TOC and HOC referenced by `tce` exist.
+fn validate_references(
+    tce: &TCE,
+    path: &str,
+    toc_data: &[TocData],
+    hoc_data: &[HocData],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    if let Some(toc_id) = &tce.tocId {
+        if !toc_data.iter().any(|toc| &toc.tocId == toc_id) {
+            issues.push(ValidationIssue::new(
+                format!("{}/tocId", path),
+                format!("TOC '{}' is not in tocData", toc_id),
+            ));
+        }
+    }
+
+    if let Some(hoc_id) = &tce.hocId {
+        if !hoc_data.iter().any(|hoc| &hoc.hocId == hoc_id) {
+            issues.push(ValidationIssue::new(
+                format!("{}/hocId", path),
+                format!("HOC '{}' is not in hocData", hoc_id),
+            ));
+        }
+    }
+}
+
+fn validate_sensor_data(
+    sensor_data: &TceSensorData,
+    path: &str,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    // Same commitment the guest checks
+    let commitment = sensor_data_commitment(&sensor_data.sensorData, &sensor_data.salt);
+    if commitment != sensor_data.commitment {
+        issues.push(ValidationIssue::new(
+            format!("{}/commitment", path),
+            "does not match the hash of sensorData and salt",
+        ));
+    }
+}
+
+fn validate_proof(proof: &ProductProof, path: &str, issues: &mut Vec<ValidationIssue>) {
+    let image_id_valid = hex::decode(&proof.imageId)
+        .map(|bytes| bytes.len() == 32)
+        .unwrap_or(false);
+    if !image_id_valid {
+        issues.push(ValidationIssue::new(
+            format!("{}/imageId", path),
+            format!("'{}' is not a 32 byte hex digest", proof.imageId),
+        ));
+    }
+
+    if proof.proofReceipt.is_empty() && proof.receiptUri.is_none() {
+        issues.push(ValidationIssue::new(
+            format!("{}/proofReceipt", path),
+            "either proofReceipt or receiptUri is required",
+        ));
+    } else if !proof.proofReceipt.is_empty()
+        && general_purpose::STANDARD
+            .decode(&proof.proofReceipt)
+            .is_err()
+    {
+        issues.push(ValidationIssue::new(
+            format!("{}/proofReceipt", path),
+            "is not valid base64",
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+//! Native PCF calculation of any document that deserializes, skipping the
+//! schema so the fuzzer reaches the arithmetic quickly.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proving_service_core::calculation::preview_pcf;
+use proving_service_core::proofing_document::ProofingDocument;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(document) = serde_json::from_slice::<ProofingDocument>(data) else {
+        return;
+    };
+
+    if let Ok(pcf) = preview_pcf(&document) {
+        assert!(pcf.is_finite(), "PCF is {}", pcf);
+    }
+});
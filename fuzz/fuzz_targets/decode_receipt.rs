@@ -0,0 +1,49 @@
+//! Upstream proof as composed by the host: `process_and_write_proofs` decodes
+//! the base64 receipt, verifies it against the latest guest's image ID and
+//! decodes its journal and signature containers.
+//!
+//! Arbitrary bytes never verify; run with `RISC0_DEV_MODE=1` and seed the
+//! corpus with fake receipts to reach the journal decoding.
+
+#![no_main]
+
+use std::sync::LazyLock;
+
+use host::allow_list::ImageAllowList;
+use host::env_helper::{process_and_write_proofs, GuestInput};
+use host::guest_registry::GuestRegistry;
+use libfuzzer_sys::fuzz_target;
+use proving_service_core::product_footprint::ProductProof;
+use tokio::runtime::Runtime;
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime")
+});
+
+fuzz_target!(|proof_receipt: &str| {
+    let registry = GuestRegistry::global();
+    let proof = ProductProof {
+        productFootprintId: "fuzz".to_string(),
+        proofReceipt: proof_receipt.to_string(),
+        pcf: 0.0,
+        proofReference: String::new(),
+        imageId: hex::encode(registry.latest().image_id),
+        guestVersion: None,
+        receiptKind: None,
+        receiptUri: None,
+        receiptDigest: None,
+        disclosureProofs: None,
+    };
+    let allow_list = ImageAllowList::new(registry.image_ids());
+    let aggregation_image_id = registry.aggregation().image_id;
+
+    // Rejected unless dev mode accepts a fake receipt, but must not panic
+    let _ = RUNTIME.block_on(process_and_write_proofs(
+        &vec![proof],
+        &allow_list,
+        aggregation_image_id,
+        &mut GuestInput::new(),
+    ));
+});
@@ -0,0 +1,12 @@
+//! Intensity strings like `"85 gCO2e/tkm"`, parsed by the guest.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proving_service_core::calculation::parse_intensity;
+
+fuzz_target!(|intensity: &str| {
+    if let Some(value) = parse_intensity(intensity) {
+        assert!(value.is_finite(), "'{}' parsed as {}", intensity, value);
+    }
+});
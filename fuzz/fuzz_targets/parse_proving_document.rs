@@ -0,0 +1,32 @@
+//! Host path of a Kafka message: `parse_proving_document` (JSON, schema
+//! validation and deserialization) and the semantic checks, followed by the
+//! native PCF preview.
+
+#![no_main]
+
+use std::sync::LazyLock;
+
+use host::parse_proving_document;
+use libfuzzer_sys::fuzz_target;
+use proving_service_core::calculation::preview_pcf;
+use proving_service_core::validation::validate;
+use tokio::runtime::Runtime;
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime")
+});
+
+fuzz_target!(|json_content: &str| {
+    let Some(document) = RUNTIME.block_on(parse_proving_document(json_content)) else {
+        return;
+    };
+    if !validate(&document).is_empty() {
+        return;
+    }
+
+    if let Ok(pcf) = preview_pcf(&document) {
+        assert!(pcf.is_finite(), "PCF of a valid document is {}", pcf);
+    }
+});
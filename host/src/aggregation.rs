@@ -0,0 +1,242 @@
+use proving_service_core::aggregation_request::AggregationRequest;
+use proving_service_core::journal::AggregationJournal;
+use proving_service_core::product_footprint::ProductProof;
+use proving_service_core::proof_container::ProofContainer;
+use risc0_zkvm::{sha::Digest, Journal, ProverOpts, Receipt};
+use tokio::time::Instant;
+use tracing::{error, info};
+
+use crate::allow_list::ImageAllowList;
+use crate::benchmarking::RunDataCollector;
+use crate::env_helper::{decode_verified_receipt, GuestInput};
+use crate::guest_registry::GuestRegistry;
+use crate::metrics::{self, FailureKind, Job};
+use crate::prover_backend::{self, ProvingLimits};
+use crate::receipt_kind::ReceiptKind;
+use crate::receipt_store::store_receipt;
+
+const DEFAULT_BATCH_SIZE: usize = 8;
+
+/// Reads `AGGREGATION_BATCH_SIZE`, the fan-in of each aggregation guest run.
+pub fn default_batch_size() -> usize {
+    std::env::var("AGGREGATION_BATCH_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+}
+
+/// Aggregates the upstream proofs of `request` in a tree of aggregation guest
+/// runs with `batchSize` receipts each, and returns a single `ProductProof`.
+/// Every run is bounded by `limits`. The cycles and proving time of all runs
+/// are added to `collector`.
+pub async fn aggregate_proofs(
+    request: AggregationRequest,
+    receipt_kind: ReceiptKind,
+    limits: ProvingLimits,
+    mut collector: Option<&mut RunDataCollector>,
+) -> Option<ProductProof> {
+    let registry = GuestRegistry::global();
+    let allow_list = ImageAllowList::from_env(registry);
+    let aggregation = registry.aggregation();
+    // Batches of one would never shrink the tree
+    let batch_size = request.batchSize.unwrap_or_else(default_batch_size).max(2);
+
+    if request.proofs.is_empty() {
+        error!("Aggregation request {} has no proofs", request.productFootprintId);
+        metrics::job_failed(FailureKind::Validation);
+        return None;
+    }
+
+    let mut level: Vec<(Digest, Receipt)> = Vec::new();
+    for pcf_proof in &request.proofs {
+        let Some(verified) = decode_verified_receipt(pcf_proof, &allow_list).await else {
+            metrics::job_failed(FailureKind::UpstreamProof);
+            return None;
+        };
+        level.push(verified);
+    }
+
+    info!(
+        "Aggregating {} proofs in batches of {}",
+        level.len(),
+        batch_size
+    );
+
+    loop {
+        let is_root = level.len() <= batch_size;
+        // Inner nodes are compressed so they stay cheap to verify as assumptions
+        let opts = if is_root {
+            receipt_kind.prover_opts()
+        } else {
+            ReceiptKind::Succinct.prover_opts()
+        };
+
+        let mut next_level = Vec::new();
+        for batch in level.chunks(batch_size) {
+            let receipt = prove_batch(
+                batch,
+                &allow_list,
+                aggregation.image_id,
+                aggregation.elf,
+                &opts,
+                limits,
+                collector.as_deref_mut(),
+            )
+            .await?;
+            next_level.push((aggregation.image_id, receipt));
+        }
+        level = next_level;
+
+        if is_root {
+            break;
+        }
+    }
+
+    let (_, receipt) = level.pop()?;
+    if let Err(e) = receipt.verify(aggregation.image_id) {
+        error!("Aggregated receipt verification failed: {}", e);
+        metrics::job_failed(FailureKind::Verification);
+        return None;
+    }
+
+    let journal = match decode_aggregation_journal(&receipt.journal, aggregation.image_id) {
+        Ok(journal) => journal,
+        Err(e) => {
+            error!("Invalid aggregation journal: {}", e);
+            metrics::job_failed(FailureKind::Verification);
+            return None;
+        }
+    };
+    info!(
+        "Aggregated PCF of {} proofs: {}",
+        journal.leaf_count, journal.pcf
+    );
+
+    let Some(stored_receipt) = store_receipt(&receipt).await else {
+        metrics::job_failed(FailureKind::ReceiptStore);
+        return None;
+    };
+
+    Some(ProductProof {
+        productFootprintId: request.productFootprintId,
+        proofReceipt: stored_receipt.proof_receipt,
+        pcf: journal.pcf,
+        proofReference: "123".to_string(),
+        imageId: hex::encode(aggregation.image_id.as_bytes()),
+        guestVersion: Some(format!("aggregation-{}", aggregation.version)),
+        receiptKind: ReceiptKind::of(&receipt).map(|kind| kind.as_str().to_string()),
+        receiptUri: stored_receipt.receipt_uri,
+        receiptDigest: Some(stored_receipt.receipt_digest),
+        disclosureProofs: None,
+    })
+}
+
+/// Decodes the journal of an aggregation guest receipt. The guest cannot know
+/// its own image ID and treats receipts of the ID it is given as inner nodes,
+/// so the journal is only valid if it names `aggregation_image_id`.
+pub fn decode_aggregation_journal(
+    journal: &Journal,
+    aggregation_image_id: Digest,
+) -> Result<AggregationJournal, String> {
+    let decoded: AggregationJournal = journal.decode().map_err(|e| e.to_string())?;
+    if decoded.aggregation_image_id != aggregation_image_id {
+        return Err(format!(
+            "aggregated with image ID {} instead of {}",
+            decoded.aggregation_image_id, aggregation_image_id
+        ));
+    }
+    Ok(decoded)
+}
+
+async fn prove_batch(
+    batch: &[(Digest, Receipt)],
+    allow_list: &ImageAllowList,
+    aggregation_image_id: Digest,
+    aggregation_elf: &'static [u8],
+    opts: &ProverOpts,
+    limits: ProvingLimits,
+    collector: Option<&mut RunDataCollector>,
+) -> Option<Receipt> {
+    let proof_containers: Vec<ProofContainer> = batch
+        .iter()
+        .map(|(image_id, receipt)| ProofContainer {
+            image_id: *image_id,
+            journal: receipt.journal.clone(),
+        })
+        .collect();
+    let serialized_proof_containers =
+        bincode::serialize(&proof_containers).expect("Failed to serialize proof_containers");
+
+    let mut input = GuestInput::new();
+    input
+        .set_session_limit(limits.cycles)
+        .write(&serialized_proof_containers)
+        .expect("Error while writing ProofContainers to guest input.")
+        .write(&allow_list.ids().to_vec())
+        .expect("Error while writing image ID allow-list to guest input.")
+        .write(&aggregation_image_id)
+        .expect("Error while writing aggregation image ID to guest input.");
+    for (_, receipt) in batch {
+        input.add_assumption(receipt.clone());
+    }
+
+    let Some(backend) = prover_backend::global() else {
+        error!("No prover backend available");
+        metrics::job_failed(FailureKind::Prover);
+        return None;
+    };
+    let start_time = Instant::now();
+    match backend
+        .prove(
+            aggregation_elf,
+            aggregation_image_id,
+            &input,
+            opts,
+            limits.timeout,
+        )
+        .await
+    {
+        Ok(info) => {
+            let duration = start_time.elapsed();
+            metrics::observe_proving(Job::Aggregation, duration, &info.stats);
+            if let Some(collector) = collector {
+                collector.add_proof_time(duration).add_cycles(&info.stats);
+            }
+            Some(info.receipt)
+        }
+        Err(e) => {
+            error!("Error while proving aggregation: {}", e);
+            metrics::job_failed(e.failure_kind());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_of(journal: &AggregationJournal) -> Journal {
+        let words = risc0_zkvm::serde::to_vec(journal).unwrap();
+        Journal::new(bytemuck::cast_slice(&words).to_vec())
+    }
+
+    #[test]
+    fn rejects_aggregations_built_with_another_image_id() {
+        let aggregation_image_id = Digest::from([7u32; 8]);
+        let journal = |image_id| {
+            journal_of(&AggregationJournal {
+                pcf: 1.0,
+                sig_containers: Vec::new(),
+                leaf_count: 1,
+                allowed_image_ids: vec![aggregation_image_id],
+                aggregation_image_id: image_id,
+            })
+        };
+
+        let decoded =
+            decode_aggregation_journal(&journal(aggregation_image_id), aggregation_image_id);
+        assert_eq!(decoded.unwrap().leaf_count, 1);
+        assert!(decode_aggregation_journal(&journal(Digest::ZERO), aggregation_image_id).is_err());
+    }
+}
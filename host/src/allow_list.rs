@@ -0,0 +1,57 @@
+use risc0_zkvm::sha::Digest;
+use tracing::warn;
+
+use crate::guest_registry::GuestRegistry;
+
+/// Image IDs of guest programs whose receipts may be composed into a new proof.
+///
+/// All guest versions of the registry are always accepted; further versions are
+/// added via `ALLOWED_IMAGE_IDS` (comma separated, hex encoded like `ProductProof.imageId`).
+#[derive(Debug, Clone)]
+pub struct ImageAllowList {
+    ids: Vec<Digest>,
+}
+
+impl ImageAllowList {
+    pub fn from_env(registry: &GuestRegistry) -> Self {
+        let configured = std::env::var("ALLOWED_IMAGE_IDS").unwrap_or_default();
+        let configured = configured
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .filter_map(|hex_id| match parse_image_id(hex_id) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    warn!("Ignoring invalid entry in ALLOWED_IMAGE_IDS: {}", e);
+                    None
+                }
+            });
+        Self::new(registry.image_ids().chain(configured))
+    }
+
+    pub fn new(ids: impl IntoIterator<Item = Digest>) -> Self {
+        let mut allow_list = ImageAllowList { ids: Vec::new() };
+        for id in ids {
+            if !allow_list.contains(&id) {
+                allow_list.ids.push(id);
+            }
+        }
+        allow_list
+    }
+
+    pub fn contains(&self, image_id: &Digest) -> bool {
+        self.ids.contains(image_id)
+    }
+
+    pub fn ids(&self) -> &[Digest] {
+        &self.ids
+    }
+}
+
+pub fn parse_image_id(hex_id: &str) -> Result<Digest, String> {
+    let bytes = hex::decode(hex_id).map_err(|e| format!("{}: {}", hex_id, e))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("{}: image ID is not 32 bytes long", hex_id))?;
+    Ok(Digest::from(bytes))
+}
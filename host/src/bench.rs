@@ -0,0 +1,320 @@
+use std::path::{Path, PathBuf};
+
+use proving_service_core::aggregation_request::AggregationRequest;
+use proving_service_core::hoc_toc_data::TransportMode;
+use proving_service_core::product_footprint::ProductProof;
+use proving_service_test_support::{ChainSpec, DocumentGenerator};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{error, info};
+
+use crate::aggregation::aggregate_proofs;
+use crate::benchmarking::RunDataCollector;
+use crate::guest_registry::GuestRegistry;
+use crate::main_proving_logic;
+use crate::output::ProvingOutcome;
+use crate::prover_backend;
+use crate::receipt_kind::ReceiptKind;
+use crate::request_options::RequestOptions;
+
+const DEFAULT_OUTPUT_DIR: &str = "benchmarks/results";
+
+/// Shape of every generated proving document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DocumentShape {
+    pub tocs: u32,
+    pub hocs: u32,
+    /// Transport legs with signed sensor data, all of them if unset.
+    #[serde(default)]
+    pub signatures: Option<u32>,
+    /// Modes of the main transport legs, all modes if empty.
+    #[serde(default)]
+    pub modes: Vec<TransportMode>,
+}
+
+impl DocumentShape {
+    fn chain_spec(&self) -> ChainSpec {
+        let mut spec = ChainSpec::new(self.tocs, self.hocs);
+        if let Some(signatures) = self.signatures {
+            spec = spec.with_signed_legs(signatures);
+        }
+        if !self.modes.is_empty() {
+            spec = spec.with_modes(self.modes.clone());
+        }
+        spec
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AggregationShape {
+    /// Number of proofs aggregated into one receipt.
+    pub fan_out: u32,
+    /// Fan-in per aggregation guest run, `AGGREGATION_BATCH_SIZE` if unset.
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+}
+
+/// Declarative benchmark, read from a JSON file.
+///
+/// Each repetition proves `aggregation.fanOut` (default 1) chains of
+/// `compositionDepth + 1` documents, where every document embeds the proof of
+/// its predecessor, and aggregates the chain heads if `aggregation` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Seeds the document generator; every repetition proves the same documents.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "default_runs")]
+    pub runs: u32,
+    /// Overrides `RECEIPT_KIND`.
+    #[serde(default)]
+    pub receipt_kind: Option<String>,
+    pub document: DocumentShape,
+    #[serde(default)]
+    pub composition_depth: u32,
+    #[serde(default)]
+    pub aggregation: Option<AggregationShape>,
+}
+
+fn default_runs() -> u32 {
+    1
+}
+
+/// Scenario and environment written at the top of the JSON report, so reports
+/// of different machines and commits can be compared.
+#[derive(Debug, Serialize)]
+struct ReportContext<'a> {
+    scenario: &'a Scenario,
+    started_at: String,
+    dev_mode: bool,
+    prover: String,
+    guest_version: String,
+    receipt_kind: String,
+    cpus: usize,
+}
+
+/// `bench <scenario.json>... [--out <dir>]` runs every scenario and writes a
+/// JSON and a CSV report per scenario to `<dir>` (default `benchmarks/results`).
+pub async fn run(args: &[String], default_options: &RequestOptions) -> bool {
+    let mut output_dir = PathBuf::from(DEFAULT_OUTPUT_DIR);
+    let mut scenario_paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--out" {
+            match args.next() {
+                Some(dir) => output_dir = PathBuf::from(dir),
+                None => return false,
+            }
+        } else {
+            scenario_paths.push(arg);
+        }
+    }
+    if scenario_paths.is_empty() {
+        return false;
+    }
+
+    let mut succeeded = true;
+    for path in scenario_paths {
+        let Some(scenario) = load_scenario(Path::new(path)) else {
+            succeeded = false;
+            continue;
+        };
+        succeeded &= run_scenario(&scenario, default_options, &output_dir).await;
+    }
+    succeeded
+}
+
+/// Reads and checks the scenario at `path`, logging why it is invalid.
+pub fn load_scenario(path: &Path) -> Option<Scenario> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Failed to read scenario {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let scenario: Scenario = match serde_json::from_str(&content) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            error!("Invalid scenario {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    if scenario.runs == 0
+        || scenario
+            .aggregation
+            .as_ref()
+            .is_some_and(|a| a.fan_out == 0)
+    {
+        error!(
+            "Invalid scenario {}: runs and fanOut must be at least 1",
+            path.display()
+        );
+        return None;
+    }
+    if let Some(kind) = &scenario.receipt_kind {
+        if ReceiptKind::from_name(kind).is_none() {
+            error!(
+                "Invalid scenario {}: unknown receipt kind '{}'",
+                path.display(),
+                kind
+            );
+            return None;
+        }
+    }
+    Some(scenario)
+}
+
+async fn run_scenario(
+    scenario: &Scenario,
+    default_options: &RequestOptions,
+    output_dir: &Path,
+) -> bool {
+    let mut options = default_options.clone();
+    if let Some(kind) = scenario
+        .receipt_kind
+        .as_deref()
+        .and_then(ReceiptKind::from_name)
+    {
+        options.receipt_kind = kind;
+    }
+    let Some(guest) = GuestRegistry::global().select(options.guest_version.as_deref()) else {
+        error!("Unknown guest version {:?}", options.guest_version);
+        return false;
+    };
+
+    let context = ReportContext {
+        scenario,
+        started_at: chrono::Utc::now().to_rfc3339(),
+        dev_mode: std::env::var("RISC0_DEV_MODE")
+            .is_ok_and(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes")),
+        prover: prover_backend::global()
+            .map_or("none", |backend| backend.name())
+            .to_string(),
+        guest_version: guest.version.to_string(),
+        receipt_kind: options.receipt_kind.available().as_str().to_string(),
+        cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    info!(
+        "Running scenario {} ({} run(s), seed {})",
+        scenario.name, scenario.runs, scenario.seed
+    );
+
+    let mut collector = RunDataCollector::new(&scenario.name);
+    for run in 1..=scenario.runs {
+        if prove_scenario(scenario, run, &options, &mut collector)
+            .await
+            .is_none()
+        {
+            error!("Scenario {} failed in run {}", scenario.name, run);
+            return false;
+        }
+    }
+
+    let csv = collector.write_to_csv(output_dir);
+    let json = collector.write_to_json(output_dir, &context);
+    match (csv, json) {
+        (Ok(csv), Ok(json)) => {
+            info!("Wrote reports {} and {}", json.display(), csv.display());
+            true
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Failed to write reports of {}: {}", scenario.name, e);
+            false
+        }
+    }
+}
+
+async fn prove_scenario(
+    scenario: &Scenario,
+    run: u32,
+    options: &RequestOptions,
+    collector: &mut RunDataCollector,
+) -> Option<()> {
+    let spec = scenario.document.chain_spec();
+    let fan_out = scenario.aggregation.as_ref().map_or(1, |a| a.fan_out);
+    // A fresh generator per run, so all repetitions prove identical documents
+    let mut generator = DocumentGenerator::new(scenario.seed);
+
+    let mut heads: Vec<ProductProof> = Vec::new();
+    for leaf in 0..fan_out {
+        let mut previous: Option<ProductProof> = None;
+        for depth in 0..=scenario.composition_depth {
+            let mut proving_document = generator.generate(&spec);
+            proving_document.proof.extend(previous.take());
+
+            collector
+                .start_new_run(run, format!("leaf-{}/depth-{}", leaf, depth))
+                .set_input(&proving_document);
+            let response =
+                match main_proving_logic(proving_document, options, Some(&mut *collector)).await? {
+                    ProvingOutcome::Proven(response) => response,
+                    ProvingOutcome::Rejected(rejection) => {
+                        error!("Guest rejected a generated document: {}", rejection.message);
+                        return None;
+                    }
+                    ProvingOutcome::Failed(failure) => {
+                        error!("Proving a generated document failed: {}", failure.message);
+                        return None;
+                    }
+                };
+            collector
+                .set_output(&response)
+                .finish_run()
+                .print_current_run();
+            previous = Some(response);
+        }
+        heads.extend(previous);
+    }
+
+    if let Some(aggregation) = &scenario.aggregation {
+        let request = AggregationRequest {
+            productFootprintId: format!("{}-aggregation", scenario.name),
+            proofs: heads,
+            batchSize: aggregation.batch_size,
+        };
+        collector
+            .start_new_run(run, "aggregation")
+            .set_input(&request);
+        let start_time = Instant::now();
+        let response = aggregate_proofs(
+            request,
+            options.receipt_kind.available(),
+            options.limits,
+            Some(&mut *collector),
+        )
+        .await?;
+        collector
+            .set_total_time(start_time.elapsed())
+            .set_output(&response)
+            .finish_run()
+            .print_current_run();
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark_scenarios_are_valid() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../benchmarks/scenarios");
+        let mut scenarios = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                assert!(load_scenario(&path).is_some(), "{}", path.display());
+                scenarios += 1;
+            }
+        }
+        assert!(scenarios > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use host::bench;
+use host::logging;
+use host::request_options::RequestOptions;
+
+/// `bench <scenario.json>... [--out <dir>]` runs benchmark scenarios and exits.
+/// Built only with the `bench` feature: `cargo run -p host --features bench --bin bench`.
+#[tokio::main]
+async fn main() {
+    logging::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: bench <scenario.json>... [--out <dir>]");
+        std::process::exit(2);
+    }
+    let succeeded = bench::run(&args, &RequestOptions::from_env()).await;
+    std::process::exit(if succeeded { 0 } else { 1 });
+}
@@ -0,0 +1,209 @@
+use proving_service_core::calculation::preview_pcf;
+use proving_service_core::journal::Rejection;
+use proving_service_core::proofing_document::ProofingDocument;
+use risc0_zkvm::default_executor;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::env_helper::{build_guest_input, decode_rejection};
+use crate::metrics::{self, FailureKind};
+use crate::request_options::RequestOptions;
+
+/// Rough CPU proving throughput used when `PROVING_CYCLES_PER_SECOND` is unset.
+const DEFAULT_PROVING_CYCLES_PER_SECOND: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentCycles {
+    /// Segment size as power of two; proving time depends on `2^po2`.
+    pub po2: u32,
+    pub user_cycles: u32,
+}
+
+/// Outcome of executing the guest without proving.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    pub product_footprint_id: String,
+    /// Zero if the guest rejected the document.
+    pub pcf: f64,
+    pub rejection: Option<Rejection>,
+    pub guest_version: String,
+    pub image_id: String,
+    pub segments: Vec<SegmentCycles>,
+    pub user_cycles: u64,
+    /// Padded cycles of all segments, which is what the prover has to prove.
+    pub total_cycles: u64,
+    pub estimated_proving_seconds: f64,
+}
+
+fn proving_cycles_per_second() -> u64 {
+    std::env::var("PROVING_CYCLES_PER_SECOND")
+        .ok()
+        .and_then(|hz| hz.parse().ok())
+        .filter(|hz| *hz > 0)
+        .unwrap_or(DEFAULT_PROVING_CYCLES_PER_SECOND)
+}
+
+/// Runs the guest with the executor instead of the prover and reports the PCF
+/// from the journal together with the cycle counts.
+///
+/// Upstream receipts are checked on the host as for proving, but the executor
+/// does not resolve assumptions, so a successful dry run does not guarantee
+/// that composition will prove.
+pub async fn dry_run(
+    mut proving_document: ProofingDocument,
+    options: &RequestOptions,
+) -> Option<DryRunReport> {
+    // Cross-check the guest against the native calculation
+    let native_pcf = preview_pcf(&proving_document);
+    let (guest, input) = build_guest_input(&mut proving_document, options).await?;
+    info!("Executing guest version {} (dry run)", guest.version);
+
+    let session = match default_executor().execute(input.executor_env(), guest.elf) {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Error while executing guest: {}", e);
+            metrics::job_failed(FailureKind::Execution);
+            return None;
+        }
+    };
+
+    let rejection = decode_rejection(guest, session.exit_code, &session.journal);
+    let pcf: f64 = match &rejection {
+        Some(rejection) => {
+            warn!(
+                "Guest rejected the document with {}: {}",
+                rejection.code, rejection.message
+            );
+            0.0
+        }
+        // Every guest version commits the PCF first
+        None => match session.journal.decode() {
+            Ok(pcf) => pcf,
+            Err(e) => {
+                error!("Failed to decode journal: {}", e);
+                metrics::job_failed(FailureKind::Execution);
+                return None;
+            }
+        },
+    };
+
+    match (native_pcf, &rejection) {
+        (Ok(native_pcf), None) if native_pcf != pcf => warn!(
+            "Guest PCF {} differs from the native calculation {}",
+            pcf, native_pcf
+        ),
+        (Err(e), None) => warn!(
+            "Guest succeeded, but the native calculation failed with {}: {}",
+            e.code(),
+            e
+        ),
+        (Ok(_), Some(_)) => warn!("Guest rejected a document the native calculation accepts"),
+        (Err(e), Some(rejection)) if e.code() != rejection.code => warn!(
+            "Guest rejected the document with {}, the native calculation with {}",
+            rejection.code,
+            e.code()
+        ),
+        _ => {}
+    }
+
+    let segments: Vec<SegmentCycles> = session
+        .segments
+        .iter()
+        .map(|segment| SegmentCycles {
+            po2: segment.po2,
+            user_cycles: segment.cycles,
+        })
+        .collect();
+    let user_cycles = segments.iter().map(|s| s.user_cycles as u64).sum();
+    let total_cycles: u64 = segments.iter().map(|s| 1u64 << s.po2).sum();
+
+    Some(DryRunReport {
+        product_footprint_id: proving_document.productFootprint.id,
+        pcf,
+        rejection,
+        guest_version: guest.version.to_string(),
+        image_id: hex::encode(guest.image_id.as_bytes()),
+        segments,
+        user_cycles,
+        total_cycles,
+        estimated_proving_seconds: total_cycles as f64 / proving_cycles_per_second() as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Expected outcome of `golden/<name>.json`, listed in `golden/cases.json`.
+    #[derive(Deserialize)]
+    struct GoldenCase {
+        #[serde(default)]
+        pcf: Option<f64>,
+        #[serde(default)]
+        error: Option<String>,
+    }
+
+    type GoldenEntry = (String, GoldenCase, ProofingDocument);
+
+    fn golden_cases() -> Result<Vec<GoldenEntry>, Box<dyn std::error::Error>> {
+        let cases: BTreeMap<String, GoldenCase> =
+            serde_json::from_str(&std::fs::read_to_string("golden/cases.json")?)?;
+        let mut golden = Vec::new();
+        for (name, case) in cases {
+            let json_content = std::fs::read_to_string(format!("golden/{}.json", name))?;
+            let proving_document: ProofingDocument = serde_json::from_str(&json_content)?;
+            golden.push((name, case, proving_document));
+        }
+        Ok(golden)
+    }
+
+    fn assert_pcf_eq(name: &str, actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-12,
+            "{}: PCF {} != expected {}",
+            name,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn golden_native_calculation() -> Result<(), Box<dyn std::error::Error>> {
+        for (name, case, proving_document) in golden_cases()? {
+            match (preview_pcf(&proving_document), case.pcf, case.error) {
+                (Ok(pcf), Some(expected), None) => assert_pcf_eq(&name, pcf, expected),
+                (Err(e), None, Some(expected)) => assert_eq!(e.code(), expected, "{}", name),
+                (result, pcf, error) => panic!(
+                    "{}: got {:?}, expected PCF {:?} or error {:?}",
+                    name, result, pcf, error
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    // Executes the guest on every golden document and checks the journal PCF
+    // or the code of the committed rejection
+    async fn golden_guest_execution() -> Result<(), Box<dyn std::error::Error>> {
+        for (name, case, proving_document) in golden_cases()? {
+            let report = dry_run(proving_document, &RequestOptions::default())
+                .await
+                .unwrap_or_else(|| panic!("{}: guest execution failed", name));
+            match (report.rejection, case.pcf, case.error) {
+                (None, Some(expected), None) => assert_pcf_eq(&name, report.pcf, expected),
+                (Some(rejection), None, Some(expected)) => {
+                    assert_eq!(rejection.code, expected, "{}", name)
+                }
+                (rejection, pcf, error) => panic!(
+                    "{}: guest returned PCF {} and {:?}, expected PCF {:?} or error {:?}",
+                    name, report.pcf, rejection, pcf, error
+                ),
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,69 +1,163 @@
+use crate::aggregation::decode_aggregation_journal;
+use crate::allow_list::{parse_image_id, ImageAllowList};
+use crate::guest_registry::{GuestProgram, GuestRegistry};
+use crate::logging::GuestLogWriter;
+use crate::metrics::{self, FailureKind};
+use crate::receipt_kind::ReceiptKind;
+use crate::receipt_store::load_receipt_bytes;
+use crate::request_options::RequestOptions;
 use crate::sig_verifier::verify_signature;
-use base64::engine::general_purpose;
-use base64::Engine;
+use proving_service_core::journal::{LegacyRejection, ProofJournal, Rejection, REJECTED_EXIT_CODE};
 use proving_service_core::product_footprint::ProductProof;
 use proving_service_core::proof_container::ProofContainer;
+use proving_service_core::proofing_document::ProofingDocument;
 use proving_service_core::sig_container::SignatureContainer;
-use risc0_zkvm::{sha::Digest, ExecutorEnvBuilder, Receipt};
+use risc0_zkvm::sha::{Digest, Digestible};
+use risc0_zkvm::{
+    ExecutorEnv, ExitCode, Journal, MaybePruned, Receipt, ReceiptClaim, VerifierContext,
+};
+use serde::Serialize;
+use tracing::{error, info, warn};
 
-pub fn process_and_write_proofs<'a>(
+/// Input and assumptions of a guest run. Unlike an `ExecutorEnv` it can be
+/// replayed, e.g. for a retry, and uploaded to a remote prover.
+#[derive(Clone, Default)]
+pub struct GuestInput {
+    bytes: Vec<u8>,
+    assumptions: Vec<Receipt>,
+    session_limit: Option<u64>,
+}
+
+impl GuestInput {
+    pub fn new() -> Self {
+        GuestInput::default()
+    }
+
+    /// Appends `data` the way `ExecutorEnvBuilder::write` does, for `env::read`.
+    pub fn write<T: Serialize>(&mut self, data: &T) -> Result<&mut Self, String> {
+        let words = risc0_zkvm::serde::to_vec(data).map_err(|e| e.to_string())?;
+        self.bytes.extend_from_slice(bytemuck::cast_slice(&words));
+        Ok(self)
+    }
+
+    pub fn add_assumption(&mut self, receipt: Receipt) -> &mut Self {
+        self.assumptions.push(receipt);
+        self
+    }
+
+    /// Stops the guest after `limit` cycles, see `ExecutorEnvBuilder::session_limit`.
+    pub fn set_session_limit(&mut self, limit: Option<u64>) -> &mut Self {
+        self.session_limit = limit;
+        self
+    }
+
+    pub fn session_limit(&self) -> Option<u64> {
+        self.session_limit
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn assumptions(&self) -> &[Receipt] {
+        &self.assumptions
+    }
+
+    /// Executor environment for local execution and proving, with the guest's
+    /// stderr forwarded to the log.
+    pub fn executor_env(&self) -> ExecutorEnv<'static> {
+        let mut builder = ExecutorEnv::builder();
+        builder
+            .stderr(GuestLogWriter::new())
+            .write_slice(&self.bytes)
+            .session_limit(self.session_limit);
+        for receipt in &self.assumptions {
+            builder.add_assumption(receipt.clone());
+        }
+        builder.build().expect("Failed to build ExecutorEnv!")
+    }
+}
+
+/// Selects the guest version requested in `options` and builds its input: the
+/// document without its upstream proofs, followed by the proof containers, the
+/// image ID allow-list and the aggregation image ID. The upstream proofs are
+/// moved out of the document.
+pub async fn build_guest_input(
+    proving_document: &mut ProofingDocument,
+    options: &RequestOptions,
+) -> Option<(&'static GuestProgram, GuestInput)> {
+    let registry = GuestRegistry::global();
+    let Some(guest) = registry.select(options.guest_version.as_deref()) else {
+        error!(
+            "Unknown guest version: {}",
+            options.guest_version.as_deref().unwrap_or_default()
+        );
+        metrics::job_failed(FailureKind::UnknownGuest);
+        return None;
+    };
+
+    // Take away the proof extension from the proving document
+    let proof_vec = std::mem::take(&mut proving_document.proof);
+
+    let mut input = GuestInput::new();
+    input
+        .set_session_limit(options.limits.cycles)
+        .write(&*proving_document)
+        .expect("Failed to write proving_document to guest input");
+
+    let allow_list = ImageAllowList::from_env(registry);
+    let aggregation_image_id = registry.aggregation().image_id;
+    if process_and_write_proofs(&proof_vec, &allow_list, aggregation_image_id, &mut input)
+        .await
+        .is_none()
+    {
+        metrics::job_failed(FailureKind::UpstreamProof);
+        return None;
+    }
+
+    Some((guest, input))
+}
+
+/// Verifies the upstream proofs, adds them as assumptions and writes their
+/// proof containers, the allow-list and the image ID of aggregated receipts.
+/// Returns `None` if a proof is rejected.
+pub async fn process_and_write_proofs(
     proof_vec: &Vec<ProductProof>,
-    env_builder: &mut ExecutorEnvBuilder<'a>,
-) {
+    allow_list: &ImageAllowList,
+    aggregation_image_id: Digest,
+    input: &mut GuestInput,
+) -> Option<()> {
     let mut proof_containers: Vec<ProofContainer> = Vec::new();
 
     // Check if the proofing document has proofs
     for pcf_proof in proof_vec {
-        println!(
+        info!(
             "Found previous proof with productFootprintId: {}",
             pcf_proof.productFootprintId
         );
-        // Decode bytes
-        let receipt_bytes: Vec<u8> = general_purpose::STANDARD
-            .decode(&pcf_proof.proofReceipt)
-            .expect(&format!(
-                "Error while decoding receipt. Id: {}.",
-                pcf_proof.productFootprintId
-            ));
-
-        // Deserialize receipt
-        let receipt: Receipt = bincode::deserialize(&receipt_bytes).expect(&format!(
-            "Error while deserializing receipt. Id: {}.",
-            pcf_proof.productFootprintId
-        ));
-
-        // Deserialize imageId
-        let image_id_vec = hex::decode(&pcf_proof.imageId).expect(&format!(
-            "Error decoding hex imageId: {}",
-            &pcf_proof.imageId
-        ));
+        let (image_id, receipt) = decode_verified_receipt(pcf_proof, allow_list).await?;
 
-        let image_id_bytes: [u8; 32] = image_id_vec
-            .try_into()
-            .expect("Image ID is not 32 bytes long");
-
-        let image_id = Digest::from(image_id_bytes);
-
-        if let Err(e) = receipt.verify(image_id) {
-            eprintln!("Receipt verification failed: {}", e);
-            return;
-        }
         // Clone Journal
         let journal = receipt.journal.clone();
 
-        // Get journal data
+        // Get journal data (leading fields of `ProofJournal`, also present in older receipts)
         let (_journal_output, serialized_sig_containers): (f64, Vec<u8>) =
             match receipt.journal.decode() {
                 Ok(data) => data,
                 Err(e) => {
-                    eprintln!("Failed to decode journal: {}", e);
-                    return;
+                    error!("Failed to decode journal: {}", e);
+                    return None;
                 }
             };
 
         let sig_containers =
-            bincode::deserialize::<Vec<SignatureContainer>>(&serialized_sig_containers)
-                .expect("Failed to deserialize signature containers");
+            match bincode::deserialize::<Vec<SignatureContainer>>(&serialized_sig_containers) {
+                Ok(sig_containers) => sig_containers,
+                Err(e) => {
+                    error!("Failed to deserialize signature containers: {}", e);
+                    return None;
+                }
+            };
 
         // verify signatures
         for sig_container in &sig_containers {
@@ -72,7 +166,7 @@ pub fn process_and_write_proofs<'a>(
                 &sig_container.signature,
                 &sig_container.pub_key,
             ) {
-                eprintln!("Signature verification failed");
+                warn!("Signature verification failed");
                 continue;
             }
         }
@@ -81,7 +175,7 @@ pub fn process_and_write_proofs<'a>(
         let proof_container = ProofContainer { image_id, journal };
 
         // Add assumption
-        env_builder.add_assumption(receipt);
+        input.add_assumption(receipt);
 
         // Append vector
         proof_containers.push(proof_container);
@@ -91,8 +185,236 @@ pub fn process_and_write_proofs<'a>(
     let serialized_proof_containers =
         bincode::serialize(&proof_containers).expect("Failed to serialize proof_containers");
 
-    // Write to env_builder
-    env_builder
+    // Write to the guest input
+    input
         .write(&serialized_proof_containers)
-        .expect("Error while writing ProofContainers to Builder.");
+        .expect("Error while writing ProofContainers to guest input.");
+
+    // The guest enforces and commits the same allow-list
+    input
+        .write(&allow_list.ids().to_vec())
+        .expect("Error while writing image ID allow-list to guest input.");
+
+    // Guests before 0.9.0 do not read it
+    input
+        .write(&aggregation_image_id)
+        .expect("Error while writing aggregation image ID to guest input.");
+    Some(())
+}
+
+/// Loads the receipt of an upstream proof (inline or from the receipt store) and
+/// verifies it against its image ID, which has to be in the allow-list.
+pub async fn decode_verified_receipt(
+    pcf_proof: &ProductProof,
+    allow_list: &ImageAllowList,
+) -> Option<(Digest, Receipt)> {
+    // Load bytes
+    let receipt_bytes: Vec<u8> = load_receipt_bytes(pcf_proof).await?;
+
+    // Deserialize receipt
+    let receipt: Receipt = match bincode::deserialize(&receipt_bytes) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            error!(
+                "Error while deserializing receipt. Id: {}. {}",
+                pcf_proof.productFootprintId, e
+            );
+            return None;
+        }
+    };
+
+    // Groth16 receipts cannot be resolved as assumptions inside the guest
+    if ReceiptKind::of(&receipt) == Some(ReceiptKind::Groth16) {
+        error!(
+            "Groth16 receipt of proof {} cannot be composed",
+            pcf_proof.productFootprintId
+        );
+        return None;
+    }
+
+    // Deserialize imageId
+    let image_id = match parse_image_id(&pcf_proof.imageId) {
+        Ok(image_id) => image_id,
+        Err(e) => {
+            error!(
+                "Invalid image ID of proof {}: {}",
+                pcf_proof.productFootprintId, e
+            );
+            return None;
+        }
+    };
+
+    if !allow_list.contains(&image_id) {
+        error!(
+            "Image ID {} of proof {} is not in the allow-list",
+            image_id, pcf_proof.productFootprintId
+        );
+        return None;
+    }
+
+    if let Err(e) = receipt.verify(image_id) {
+        error!("Receipt verification failed: {}", e);
+        return None;
+    }
+
+    // A receipt is only as trustworthy as the aggregated receipts it composed
+    let registry = GuestRegistry::global();
+    let aggregation_image_id = registry.aggregation().image_id;
+    let checked = if image_id == aggregation_image_id {
+        decode_aggregation_journal(&receipt.journal, aggregation_image_id).map(|_| ())
+    } else {
+        match registry.program(&image_id) {
+            Some(guest) => check_aggregation_image_id(guest, &receipt.journal),
+            // Layout of guests from `ALLOWED_IMAGE_IDS` is unknown
+            None => Ok(()),
+        }
+    };
+    if let Err(e) = checked {
+        error!(
+            "Invalid journal of proof {}: {}",
+            pcf_proof.productFootprintId, e
+        );
+        return None;
+    }
+
+    Some((image_id, receipt))
+}
+
+/// Checks that a receipt of `guest` composed aggregated receipts by the image ID
+/// of the registry's aggregation guest, for versions that commit it.
+pub fn check_aggregation_image_id(guest: &GuestProgram, journal: &Journal) -> Result<(), String> {
+    if !guest.commits_aggregation_image_id() {
+        return Ok(());
+    }
+    let journal: ProofJournal = journal.decode().map_err(|e| e.to_string())?;
+    let expected = GuestRegistry::global().aggregation().image_id;
+    if journal.aggregation_image_id != expected {
+        return Err(format!(
+            "composed aggregations of image ID {} instead of {}",
+            journal.aggregation_image_id, expected
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the rejection `guest` committed instead of its journal, if it halted
+/// with `REJECTED_EXIT_CODE`.
+pub fn decode_rejection(
+    guest: &GuestProgram,
+    exit_code: ExitCode,
+    journal: &Journal,
+) -> Option<Rejection> {
+    if !guest.commits_rejections() || exit_code != ExitCode::Halted(REJECTED_EXIT_CODE.into()) {
+        return None;
+    }
+    let decoded = if guest.rejections_name_tce() {
+        journal.decode()
+    } else {
+        journal.decode::<LegacyRejection>().map(Rejection::from)
+    };
+    match decoded {
+        Ok(rejection) => Some(rejection),
+        Err(e) => {
+            error!("Failed to decode rejection: {}", e);
+            None
+        }
+    }
+}
+
+/// Verifies that `receipt` proves a run of `image_id` that halted with
+/// `REJECTED_EXIT_CODE` and committed its journal.
+pub fn verify_rejection(receipt: &Receipt, image_id: Digest) -> Result<(), String> {
+    receipt
+        .verify_integrity_with_context(&VerifierContext::default())
+        .map_err(|e| e.to_string())?;
+
+    // Same claim as `Receipt::verify` expects, apart from the exit code
+    let mut expected_claim =
+        ReceiptClaim::ok(image_id, MaybePruned::Pruned(receipt.journal.digest()));
+    expected_claim.exit_code = ExitCode::Halted(REJECTED_EXIT_CODE.into());
+    let claim = receipt.claim().map_err(|e| e.to_string())?;
+    if claim.digest() != expected_claim.digest() {
+        return Err("receipt claim does not match a rejection".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest(version: &'static str) -> GuestProgram {
+        GuestProgram {
+            version,
+            elf: &[],
+            image_id: Digest::ZERO,
+        }
+    }
+
+    fn journal_of<T: Serialize>(value: &T) -> Journal {
+        let words = risc0_zkvm::serde::to_vec(value).unwrap();
+        Journal::new(bytemuck::cast_slice(&words).to_vec())
+    }
+
+    #[test]
+    fn rejects_proofs_composed_with_another_aggregation_image_id() {
+        let journal = |aggregation_image_id| {
+            journal_of(&ProofJournal {
+                pcf: 1.0,
+                sig_containers: Vec::new(),
+                product_footprint_id: "footprint".to_string(),
+                shipment_id: "shipment".to_string(),
+                document_hash: Digest::ZERO,
+                toc_data_hash: Digest::ZERO,
+                hoc_data_hash: Digest::ZERO,
+                tce_root: Digest::ZERO,
+                toc_root: Digest::ZERO,
+                hoc_root: Digest::ZERO,
+                allowed_image_ids: Vec::new(),
+                aggregation_image_id,
+            })
+        };
+        let aggregation_image_id = GuestRegistry::global().aggregation().image_id;
+        let forged = journal(Digest::from([7u32; 8]));
+
+        assert!(
+            check_aggregation_image_id(&guest("0.9.0"), &journal(aggregation_image_id)).is_ok()
+        );
+        assert!(check_aggregation_image_id(&guest("0.9.0"), &forged).is_err());
+        // Older guests do not commit it
+        assert!(check_aggregation_image_id(&guest("0.8.0"), &forged).is_ok());
+    }
+
+    #[test]
+    fn decodes_rejections_in_the_layout_of_their_guest() {
+        let rejected = ExitCode::Halted(REJECTED_EXIT_CODE.into());
+        let rejection = Rejection {
+            code: "unknownToc".to_string(),
+            tce_id: Some("tce-1".to_string()),
+            message: "unknown TOC".to_string(),
+        };
+        let legacy = LegacyRejection {
+            code: rejection.code.clone(),
+            message: rejection.message.clone(),
+        };
+
+        let current = journal_of(&rejection);
+        assert_eq!(
+            decode_rejection(&guest("0.8.0"), rejected, &current),
+            Some(rejection.clone())
+        );
+        assert_eq!(
+            decode_rejection(&guest("0.8.0"), ExitCode::Halted(0), &current),
+            None
+        );
+        assert_eq!(decode_rejection(&guest("0.6.0"), rejected, &current), None);
+
+        assert_eq!(
+            decode_rejection(&guest("0.7.0"), rejected, &journal_of(&legacy)),
+            Some(Rejection {
+                tce_id: None,
+                ..rejection
+            })
+        );
+    }
 }
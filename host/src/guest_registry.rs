@@ -0,0 +1,189 @@
+use std::sync::LazyLock;
+
+use methods::{
+    ARCHIVED_GUESTS, GUEST_AGGREGATION_ELF, GUEST_AGGREGATION_ID, GUEST_AGGREGATION_VERSION,
+    GUEST_PROOFING_LOGIC_ELF, GUEST_PROOFING_LOGIC_ID, GUEST_PROOFING_LOGIC_VERSION,
+};
+use risc0_zkvm::{compute_image_id, sha::Digest};
+
+use crate::allow_list::parse_image_id;
+
+pub struct GuestProgram {
+    pub version: &'static str,
+    pub elf: &'static [u8],
+    pub image_id: Digest,
+}
+
+impl GuestProgram {
+    pub fn is_latest(&self) -> bool {
+        self.version == GUEST_PROOFING_LOGIC_VERSION
+    }
+
+    /// Archived versions pinned without their binary can only be verified.
+    pub fn is_provable(&self) -> bool {
+        self.is_latest() || !self.elf.is_empty()
+    }
+
+    /// Guests before 0.7.0 panic on invalid documents instead of committing a
+    /// `Rejection` and halting with `REJECTED_EXIT_CODE`.
+    pub fn commits_rejections(&self) -> bool {
+        self.is_at_least(0, 7)
+    }
+
+    /// Guests 0.7.x commit a `LegacyRejection` without the offending TCE.
+    pub fn rejections_name_tce(&self) -> bool {
+        self.is_at_least(0, 8)
+    }
+
+    /// Guests before 0.9.0 compose aggregated receipts without committing the
+    /// aggregation image ID they trusted.
+    pub fn commits_aggregation_image_id(&self) -> bool {
+        self.is_at_least(0, 9)
+    }
+
+    fn is_at_least(&self, major: u32, minor: u32) -> bool {
+        let mut parts = self.version.split('.').map(|part| part.parse::<u32>().ok());
+        (parts.next().flatten(), parts.next().flatten()) >= (Some(major), Some(minor))
+    }
+}
+
+/// All guest versions shipped with the `methods` crate, latest first, plus the
+/// aggregation guest.
+pub struct GuestRegistry {
+    programs: Vec<GuestProgram>,
+    aggregation: GuestProgram,
+}
+
+static REGISTRY: LazyLock<GuestRegistry> = LazyLock::new(GuestRegistry::load);
+
+impl GuestRegistry {
+    pub fn global() -> &'static GuestRegistry {
+        &REGISTRY
+    }
+
+    fn load() -> Self {
+        let mut programs = vec![GuestProgram {
+            version: GUEST_PROOFING_LOGIC_VERSION,
+            elf: GUEST_PROOFING_LOGIC_ELF,
+            image_id: Digest::from(GUEST_PROOFING_LOGIC_ID),
+        }];
+
+        for archived in ARCHIVED_GUESTS.iter().rev() {
+            if archived.version == GUEST_PROOFING_LOGIC_VERSION {
+                continue;
+            }
+            let image_id = parse_image_id(archived.image_id).unwrap_or_else(|e| {
+                panic!(
+                    "Invalid image ID of archived guest {}: {}",
+                    archived.version, e
+                )
+            });
+            if !archived.elf.is_empty() {
+                let elf_image_id = compute_image_id(archived.elf).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to compute image ID of archived guest {}: {}",
+                        archived.version, e
+                    )
+                });
+                assert_eq!(
+                    elf_image_id, image_id,
+                    "Binary of archived guest {} does not match its pinned image ID",
+                    archived.version
+                );
+            }
+            programs.push(GuestProgram {
+                version: archived.version,
+                elf: archived.elf,
+                image_id,
+            });
+        }
+
+        let aggregation = GuestProgram {
+            version: GUEST_AGGREGATION_VERSION,
+            elf: GUEST_AGGREGATION_ELF,
+            image_id: Digest::from(GUEST_AGGREGATION_ID),
+        };
+
+        GuestRegistry {
+            programs,
+            aggregation,
+        }
+    }
+
+    pub fn latest(&self) -> &GuestProgram {
+        &self.programs[0]
+    }
+
+    /// Selects the requested guest version, or the latest if none is given.
+    pub fn select(&self, version: Option<&str>) -> Option<&GuestProgram> {
+        match version {
+            Some(version) => self
+                .programs
+                .iter()
+                .find(|p| p.version == version && p.is_provable()),
+            None => Some(self.latest()),
+        }
+    }
+
+    /// All proofing guest versions, latest first.
+    pub fn programs(&self) -> impl Iterator<Item = &GuestProgram> {
+        self.programs.iter()
+    }
+
+    /// The proofing guest version with `image_id`.
+    pub fn program(&self, image_id: &Digest) -> Option<&GuestProgram> {
+        self.programs.iter().find(|p| p.image_id == *image_id)
+    }
+
+    pub fn aggregation(&self) -> &GuestProgram {
+        &self.aggregation
+    }
+
+    /// Image IDs of all proofing guest versions and of the aggregation guest.
+    pub fn image_ids(&self) -> impl Iterator<Item = Digest> + '_ {
+        self.programs
+            .iter()
+            .chain(std::iter::once(&self.aggregation))
+            .map(|p| p.image_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allow_list::ImageAllowList;
+
+    /// Image ID of the guest released before guest versions were archived, found
+    /// in the proofs of `json-examples/test_3_1_1.json`.
+    const BASELINE_IMAGE_ID: &str =
+        "82ee7fbcae30611a3537475793efea3d55b8dda751fc10b7c7dabc85cec70819";
+
+    #[test]
+    fn baseline_guest_is_registered_and_allowed() {
+        let registry = GuestRegistry::global();
+        let image_id = parse_image_id(BASELINE_IMAGE_ID).unwrap();
+
+        let baseline = registry
+            .program(&image_id)
+            .expect("baseline guest is not registered");
+        assert_eq!(baseline.version, "0.1.0");
+        assert!(!baseline.commits_rejections());
+        assert!(!baseline.commits_aggregation_image_id());
+        assert!(ImageAllowList::from_env(registry).contains(&image_id));
+    }
+
+    #[test]
+    fn rejections_are_committed_since_0_7_0() {
+        let guest = |version| GuestProgram {
+            version,
+            elf: &[],
+            image_id: Digest::ZERO,
+        };
+        assert!(!guest("0.6.0").commits_rejections());
+        assert!(guest("0.7.0").commits_rejections());
+        assert!(guest("1.0.0").commits_rejections());
+        assert!(guest("0.10.0").commits_rejections());
+        assert!(!guest("0.7.0").rejections_name_tce());
+        assert!(guest("0.8.0").rejections_name_tce());
+    }
+}
@@ -0,0 +1,197 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+
+use crate::allow_list::parse_image_id;
+use crate::guest_registry::GuestRegistry;
+use crate::output::OutputMode;
+use crate::prover_backend::{self, ProverBackend};
+use crate::receipt_kind::ReceiptKind;
+use crate::receipt_store;
+use crate::wire_format::WireFormat;
+
+static KAFKA_CONNECTED: AtomicBool = AtomicBool::new(false);
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static PROVER_BACKEND_ANSWERS: AtomicBool = AtomicBool::new(false);
+static PROVER_AVAILABLE: LazyLock<bool> = LazyLock::new(check_prover);
+static CONFIG_ISSUES: LazyLock<Vec<String>> = LazyLock::new(config_issues);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    pub ready: bool,
+    pub kafka_connected: bool,
+    pub prover_available: bool,
+    pub config_issues: Vec<String>,
+    pub shutting_down: bool,
+}
+
+/// Only reads the state of the background checks, so probes answer right away.
+pub fn readiness() -> Readiness {
+    let kafka_connected = KAFKA_CONNECTED.load(Ordering::Relaxed);
+    let shutting_down = SHUTTING_DOWN.load(Ordering::Relaxed);
+    let prover_available = *PROVER_AVAILABLE && PROVER_BACKEND_ANSWERS.load(Ordering::Relaxed);
+    let config_issues = CONFIG_ISSUES.clone();
+
+    Readiness {
+        ready: kafka_connected && prover_available && config_issues.is_empty() && !shutting_down,
+        kafka_connected,
+        prover_available,
+        config_issues,
+        shutting_down,
+    }
+}
+
+pub fn set_kafka_connected(connected: bool) {
+    KAFKA_CONNECTED.store(connected, Ordering::Relaxed);
+}
+
+/// Logs configuration issues once at startup; they also fail `/readyz`.
+pub fn check_config() {
+    for issue in CONFIG_ISSUES.iter() {
+        error!("Invalid configuration: {}", issue);
+    }
+    if !*PROVER_AVAILABLE {
+        error!("Prover is not available");
+    }
+}
+
+/// Listens for SIGTERM and Ctrl-C in a task of its own, so the service is
+/// marked as no longer ready right away, even while a job is in flight. The
+/// returned future resolves once the signal arrived.
+pub fn shutdown_signal() -> impl Future<Output = ()> {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    let (requested, shutdown) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        SHUTTING_DOWN.store(true, Ordering::Relaxed);
+        info!("Shutdown requested, finishing the in-flight job");
+        let _ = requested.send(());
+    });
+    async move {
+        let _ = shutdown.await;
+    }
+}
+
+/// Checks that every guest can be loaded and that a prover backend is set up.
+fn check_prover() -> bool {
+    // Loading the registry checks archived binaries against their pinned image IDs
+    let Ok(registry) = std::panic::catch_unwind(GuestRegistry::global) else {
+        return false;
+    };
+    // Guests built with `RISC0_SKIP_BUILD` have no ELF to prove
+    let missing: Vec<&str> = [registry.latest(), registry.aggregation()]
+        .into_iter()
+        .filter(|program| program.elf.is_empty())
+        .map(|program| program.version)
+        .collect();
+    if !missing.is_empty() {
+        error!("Guest versions {} have no ELF", missing.join(", "));
+        return false;
+    }
+    prover_backend::global().is_some()
+}
+
+/// Periodically checks that `r0vm` runs or that the remote prover answers,
+/// which is reported by `/readyz`. A check may take several request timeouts,
+/// far longer than a probe waits.
+pub async fn monitor_prover_backend() {
+    loop {
+        PROVER_BACKEND_ANSWERS.store(check_prover_backend().await, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_secs(15)).await;
+    }
+}
+
+async fn check_prover_backend() -> bool {
+    let Some(backend) = prover_backend::global() else {
+        return false;
+    };
+    match backend.check().await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Prover backend {} is not available: {}", backend.name(), e);
+            false
+        }
+    }
+}
+
+fn config_issues() -> Vec<String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let mut issues = Vec::new();
+
+    if let Some(mode) = var("OUTPUT_MODE") {
+        if OutputMode::from_name(&mode).is_none() {
+            issues.push(format!("OUTPUT_MODE: unknown output mode '{}'", mode));
+        }
+    }
+    if let Some(kind) = var("RECEIPT_KIND") {
+        if ReceiptKind::from_name(&kind).is_none() {
+            issues.push(format!("RECEIPT_KIND: unknown receipt kind '{}'", kind));
+        }
+    }
+    if let Some(content_type) = var("OUTPUT_CONTENT_TYPE") {
+        if WireFormat::from_content_type(&content_type).is_none() {
+            issues.push(format!(
+                "OUTPUT_CONTENT_TYPE: unknown content type '{}'",
+                content_type
+            ));
+        }
+    }
+    if let Some(version) = var("GUEST_VERSION") {
+        if GuestRegistry::global().select(Some(&version)).is_none() {
+            issues.push(format!(
+                "GUEST_VERSION: guest version '{}' is unknown or not provable",
+                version
+            ));
+        }
+    }
+    let allowed_image_ids = var("ALLOWED_IMAGE_IDS").unwrap_or_default();
+    for entry in allowed_image_ids.split(',').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+        if let Err(e) = parse_image_id(entry) {
+            issues.push(format!("ALLOWED_IMAGE_IDS: {}", e));
+        }
+    }
+    match var("RECEIPT_STORE").as_deref() {
+        None | Some("inline") => {}
+        Some("fs") | Some("s3") => {
+            if let Some(e) = receipt_store::setup_error() {
+                issues.push(format!("RECEIPT_STORE: {}", e));
+            }
+        }
+        Some(other) => issues.push(format!("RECEIPT_STORE: unknown receipt store '{}'", other)),
+    }
+    match var("PROOF_CACHE").as_deref() {
+        None | Some("") | Some("none") | Some("disk") => {}
+        Some(other) => issues.push(format!("PROOF_CACHE: unknown proof cache '{}'", other)),
+    }
+    if let Err(e) = ProverBackend::from_env() {
+        issues.push(format!("PROVER_BACKEND: {}", e));
+    }
+    for name in [
+        "AGGREGATION_BATCH_SIZE",
+        "HTTP_PORT",
+        "PROVING_CYCLES_PER_SECOND",
+        "PROVING_CYCLE_LIMIT",
+        "PROVING_TIMEOUT_SECS",
+        "PROOF_CACHE_TTL_SECS",
+    ] {
+        if let Some(value) = var(name) {
+            if value.parse::<u64>().is_err() {
+                issues.push(format!("{}: '{}' is not a number", name, value));
+            }
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,104 @@
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use proving_service_core::proofing_document::ProofingDocument;
+use proving_service_core::validation::validate;
+use tokio::net::TcpListener;
+use tracing::{error, info, Instrument};
+
+use crate::dry_run::dry_run;
+use crate::health;
+use crate::logging;
+use crate::metrics;
+use crate::request_options::RequestOptions;
+
+const DEFAULT_HTTP_PORT: u16 = 3000;
+
+fn router(default_options: RequestOptions) -> Router {
+    Router::new()
+        .route("/dry-run", post(dry_run_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .with_state(default_options)
+}
+
+/// Serves the HTTP API on `HTTP_PORT` (default 3000).
+pub async fn serve(default_options: RequestOptions) {
+    let port = std::env::var("HTTP_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_HTTP_PORT);
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind HTTP port {}: {}", port, e);
+            return;
+        }
+    };
+    info!("HTTP API listening on port {}", port);
+
+    if let Err(e) = axum::serve(listener, router(default_options)).await {
+        error!("HTTP server error: {}", e);
+    }
+}
+
+/// The process is alive as long as it answers.
+async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+async fn readyz_handler() -> Response {
+    let readiness = health::readiness();
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness)).into_response()
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+async fn dry_run_handler(
+    State(options): State<RequestOptions>,
+    headers: HeaderMap,
+    Json(proving_document): Json<ProofingDocument>,
+) -> Response {
+    let correlation_id = headers
+        .get("correlation-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("http-{}", chrono::Utc::now().timestamp_millis()));
+    let span = logging::job_span(&correlation_id);
+
+    let issues = validate(&proving_document);
+    if !issues.is_empty() {
+        let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(issues)).into_response();
+    }
+
+    // The executor environment is not `Send`, so execute on a blocking thread
+    let handle = tokio::runtime::Handle::current();
+    let report = tokio::task::spawn_blocking(move || {
+        handle.block_on(dry_run(proving_document, &options).instrument(span))
+    })
+    .await;
+
+    match report {
+        Ok(Some(report)) if report.rejection.is_some() => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response()
+        }
+        Ok(Some(report)) => Json(report).into_response(),
+        Ok(None) => (StatusCode::UNPROCESSABLE_ENTITY, "Guest execution failed").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
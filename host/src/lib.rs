@@ -0,0 +1,590 @@
+use env_helper::{
+    build_guest_input, check_aggregation_image_id, decode_rejection, verify_rejection,
+};
+use proving_service_core::aggregation_request::AggregationRequest;
+use proving_service_core::disclosure::DisclosureProofs;
+use proving_service_core::journal::{ProofJournal, Rejection};
+use proving_service_core::product_footprint::ProductProof;
+use proving_service_core::proofing_document::*;
+use proving_service_core::validation::validate;
+use risc0_zkvm::Receipt;
+use serde::de::DeserializeOwned;
+use serde_path_to_error::deserialize;
+use std::fs::File;
+use std::io::Write;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::aggregation::aggregate_proofs;
+use crate::allow_list::ImageAllowList;
+use crate::benchmarking::RunDataCollector;
+use crate::dry_run::dry_run;
+use crate::guest_registry::{GuestProgram, GuestRegistry};
+use crate::metrics::{FailureKind, Job};
+use crate::output::{ProvenRejection, ProvingFailure, ProvingOutcome, ProvingResponse};
+use crate::proof_cache::ProofCache;
+use crate::prover_backend::ProverError;
+use crate::receipt_kind::ReceiptKind;
+use crate::receipt_store::{store_receipt, StoredReceipt};
+use crate::request_options::RequestOptions;
+use crate::schema_validation::validate_proofing_document;
+use crate::wire_format::WireFormat;
+
+pub mod aggregation;
+pub mod allow_list;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod benchmarking;
+pub mod dry_run;
+pub mod env_helper;
+pub mod guest_registry;
+pub mod health;
+pub mod http;
+pub mod logging;
+pub mod metrics;
+pub mod output;
+pub mod proof_cache;
+pub mod prover_backend;
+pub mod receipt_kind;
+pub mod receipt_store;
+pub mod request_options;
+pub mod schema_validation;
+pub mod sig_verifier;
+pub mod wire_format;
+
+const DEBUG: bool = false;
+
+pub async fn process_message(payload: &[u8], options: &RequestOptions) -> Option<ProvingResponse> {
+    if options.content_type != WireFormat::Json {
+        return handle_binary_message(payload, options).await;
+    }
+
+    match std::str::from_utf8(payload) {
+        Ok(payload_str) => process_payload(payload_str, options).await,
+        Err(e) => {
+            error!("Payload UTF-8 error: {}", e);
+            metrics::job_failed(FailureKind::InvalidDocument);
+            None
+        }
+    }
+}
+
+async fn process_payload(payload_str: &str, options: &RequestOptions) -> Option<ProvingResponse> {
+    // Some producers send the document as a stringified JSON string, unwrap it first
+    match serde_json::from_str::<String>(payload_str) {
+        Ok(inner_json_str) => handle_kafka_message(&inner_json_str, options).await,
+        Err(_) => handle_kafka_message(payload_str, options).await,
+    }
+}
+
+pub async fn main_proving_logic(
+    mut proving_document: ProofingDocument,
+    options: &RequestOptions,
+    collector: Option<&mut RunDataCollector>,
+) -> Option<ProvingOutcome> {
+    let total_start_time = Instant::now();
+
+    info!(
+        "Received proving document with ID: {}",
+        proving_document.productFootprint.id
+    );
+    info!(
+        "From Company: {}",
+        proving_document.productFootprint.companyName
+    );
+
+    let (guest, input) = build_guest_input(&mut proving_document, options).await?;
+    info!("Proving with guest version {}", guest.version);
+
+    // Start the proving process
+    let Some(backend) = prover_backend::global() else {
+        error!("No prover backend available");
+        metrics::job_failed(FailureKind::Prover);
+        return None;
+    };
+    let receipt_kind = options.receipt_kind.available();
+    info!("ELF size: {}", guest.elf.len());
+    info!("Receipt kind: {}", receipt_kind.as_str());
+
+    let proof_start_time = Instant::now();
+
+    // Rejections halt with a non-zero exit code, which is only proven on request
+    let prover_opts = receipt_kind
+        .prover_opts()
+        .with_prove_guest_errors(guest.commits_rejections());
+    let prove_info = match backend
+        .prove(
+            guest.elf,
+            guest.image_id,
+            &input,
+            &prover_opts,
+            options.limits.timeout,
+        )
+        .await
+    {
+        Ok(info) => info,
+        // Aborted jobs are reported, so a hanging document does not look lost
+        Err(e @ (ProverError::Timeout(_) | ProverError::CycleLimit(_))) => {
+            error!("Proving aborted: {}", e);
+            metrics::job_failed(e.failure_kind());
+            return Some(ProvingOutcome::Failed(ProvingFailure {
+                product_footprint_id: proving_document.productFootprint.id,
+                kind: e.failure_kind().as_str().to_string(),
+                message: e.to_string(),
+            }));
+        }
+        Err(e) => {
+            error!("Error while proving: {}", e);
+            metrics::job_failed(e.failure_kind());
+            return None;
+        }
+    };
+
+    let duration = proof_start_time.elapsed();
+    metrics::observe_proving(Job::Proof, duration, &prove_info.stats);
+
+    let receipt = prove_info.receipt;
+
+    // Invalid documents are proven with the reason instead of a PCF
+    let exit_code = receipt
+        .claim()
+        .ok()
+        .and_then(|claim| claim.as_value().ok().map(|claim| claim.exit_code));
+    let rejection =
+        exit_code.and_then(|exit_code| decode_rejection(guest, exit_code, &receipt.journal));
+    if let Some(rejection) = rejection {
+        warn!(
+            "Guest rejected the document with {}: {}",
+            rejection.code, rejection.message
+        );
+        let proven_rejection = prove_rejection(
+            proving_document.productFootprint.id,
+            guest,
+            &receipt,
+            rejection,
+        )
+        .await?;
+
+        if let Some(collector) = collector {
+            collector
+                .set_total_time(total_start_time.elapsed())
+                .add_proof_time(duration)
+                .add_cycles(&prove_info.stats);
+        }
+        return Some(ProvingOutcome::Rejected(proven_rejection));
+    }
+
+    // Every guest version commits the PCF first
+    let journal_output: f64 = match receipt.journal.decode() {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to decode journal: {}", e);
+            metrics::job_failed(FailureKind::Verification);
+            return None;
+        }
+    };
+
+    if let Err(e) = receipt.verify(guest.image_id) {
+        error!("Receipt verification failed: {}", e);
+        metrics::job_failed(FailureKind::Verification);
+        return None;
+    }
+
+    if let Err(e) = check_aggregation_image_id(guest, &receipt.journal) {
+        error!("Invalid journal: {}", e);
+        metrics::job_failed(FailureKind::Verification);
+        return None;
+    }
+
+    let Some(stored_receipt) = store_receipt(&receipt).await else {
+        metrics::job_failed(FailureKind::ReceiptStore);
+        return None;
+    };
+
+    info!("PCF Value from Journal: {}", journal_output);
+    // Older guest versions commit neither document hashes nor Merkle roots
+    let disclosure_proofs = if guest.is_latest() {
+        match receipt.journal.decode::<ProofJournal>() {
+            Ok(journal) => info!("Document hash from Journal: {}", journal.document_hash),
+            Err(e) => error!("Failed to decode journal: {}", e),
+        }
+        Some(DisclosureProofs::for_document(&proving_document))
+    } else {
+        None
+    };
+
+    info!("Handed over response");
+
+    let proof_respone = product_proof(
+        proving_document.productFootprint.id,
+        guest,
+        &receipt,
+        journal_output,
+        stored_receipt,
+        disclosure_proofs,
+    );
+
+    if DEBUG {
+        // Write Output to file (for debugging purposes)
+        let json_string = serde_json::to_string_pretty(&proof_respone).ok()?;
+        let mut file = File::create("latest_output.json").ok()?;
+        file.write_all(&json_string.as_bytes()).ok()?;
+    }
+
+    if let Some(collector) = collector {
+        collector
+            .set_total_time(total_start_time.elapsed())
+            .add_proof_time(duration)
+            .add_cycles(&prove_info.stats);
+    }
+
+    Some(ProvingOutcome::Proven(proof_respone))
+}
+
+/// Describes the verified `receipt` of `guest`. The receipt kind is taken from
+/// the receipt itself, as Bonsai returns succinct receipts for composite requests.
+fn product_proof(
+    product_footprint_id: String,
+    guest: &GuestProgram,
+    receipt: &Receipt,
+    pcf: f64,
+    stored_receipt: StoredReceipt,
+    disclosure_proofs: Option<DisclosureProofs>,
+) -> ProductProof {
+    ProductProof {
+        productFootprintId: product_footprint_id,
+        proofReceipt: stored_receipt.proof_receipt,
+        proofReference: "123".to_string(),
+        pcf,
+        imageId: hex::encode(guest.image_id.as_bytes()),
+        guestVersion: Some(guest.version.to_string()),
+        receiptKind: ReceiptKind::of(receipt).map(|kind| kind.as_str().to_string()),
+        receiptUri: stored_receipt.receipt_uri,
+        receiptDigest: Some(stored_receipt.receipt_digest),
+        disclosureProofs: disclosure_proofs,
+    }
+}
+
+/// Verifies and stores the receipt of a document the guest rejected.
+async fn prove_rejection(
+    product_footprint_id: String,
+    guest: &GuestProgram,
+    receipt: &Receipt,
+    rejection: Rejection,
+) -> Option<ProvenRejection> {
+    if let Err(e) = verify_rejection(receipt, guest.image_id) {
+        error!("Rejection receipt verification failed: {}", e);
+        metrics::job_failed(FailureKind::Verification);
+        return None;
+    }
+
+    let Some(stored_receipt) = store_receipt(receipt).await else {
+        metrics::job_failed(FailureKind::ReceiptStore);
+        return None;
+    };
+
+    Some(ProvenRejection {
+        product_footprint_id,
+        code: rejection.code,
+        tce_id: rejection.tce_id,
+        message: rejection.message,
+        proof_receipt: stored_receipt.proof_receipt,
+        receipt_uri: stored_receipt.receipt_uri,
+        receipt_digest: stored_receipt.receipt_digest,
+        image_id: hex::encode(guest.image_id.as_bytes()),
+        guest_version: guest.version.to_string(),
+    })
+}
+
+pub async fn parse_proving_document(json_content: &str) -> Option<ProofingDocument> {
+    let value: serde_json::Value = match serde_json::from_str(json_content) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to parse message as JSON: {}", e);
+            metrics::job_failed(FailureKind::InvalidDocument);
+            return None;
+        }
+    };
+
+    // Report every violation at once instead of the first serde error
+    let violations = validate_proofing_document(&value);
+    if !violations.is_empty() {
+        error!(
+            "Proving document has {} schema violation(s):",
+            violations.len()
+        );
+        for violation in &violations {
+            error!("  {}", violation);
+        }
+        metrics::job_failed(FailureKind::InvalidDocument);
+        return None;
+    }
+
+    parse_json(json_content)
+}
+
+fn parse_json<T: DeserializeOwned>(json_content: &str) -> Option<T> {
+    let mut de = serde_json::Deserializer::from_str(json_content);
+    match deserialize(&mut de) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!(
+                "Failed to deserialize message at path '{}': {}",
+                e.path(),
+                e
+            );
+            metrics::job_failed(FailureKind::InvalidDocument);
+            None
+        }
+    }
+}
+
+pub async fn handle_kafka_message(
+    shipments_json: &str,
+    options: &RequestOptions,
+) -> Option<ProvingResponse> {
+    info!("Received message");
+
+    if options.aggregate {
+        let request: AggregationRequest = parse_json(shipments_json)?;
+        return handle_aggregation_request(request, options).await;
+    }
+
+    let proving_document = parse_proving_document(shipments_json).await?;
+
+    handle_proving_document(proving_document, options).await
+}
+
+/// Handles a CBOR or postcard encoded payload.
+async fn handle_binary_message(
+    payload: &[u8],
+    options: &RequestOptions,
+) -> Option<ProvingResponse> {
+    info!("Received {} message", options.content_type.content_type());
+
+    if options.aggregate {
+        let request: AggregationRequest = decode_payload(payload, options.content_type)?;
+        return handle_aggregation_request(request, options).await;
+    }
+
+    let proving_document: ProofingDocument = decode_payload(payload, options.content_type)?;
+    handle_proving_document(proving_document, options).await
+}
+
+fn decode_payload<T: DeserializeOwned>(payload: &[u8], format: WireFormat) -> Option<T> {
+    match format.decode(payload) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("Failed to decode {} message: {}", format.content_type(), e);
+            metrics::job_failed(FailureKind::InvalidDocument);
+            None
+        }
+    }
+}
+
+async fn handle_aggregation_request(
+    request: AggregationRequest,
+    options: &RequestOptions,
+) -> Option<ProvingResponse> {
+    logging::record_document(&request.productFootprintId, None);
+    let receipt_kind = options.receipt_kind.available();
+    let product_proof = aggregate_proofs(request, receipt_kind, options.limits, None).await?;
+    metrics::job_succeeded(Job::Aggregation);
+    Some(ProvingResponse::Proof(product_proof))
+}
+
+async fn handle_proving_document(
+    proving_document: ProofingDocument,
+    options: &RequestOptions,
+) -> Option<ProvingResponse> {
+    logging::record_document(
+        &proving_document.productFootprint.id,
+        proving_document
+            .productFootprint
+            .extensions
+            .first()
+            .map(|extension| extension.data.shipmentId.as_str()),
+    );
+
+    // Reject documents the guest would reject before setting up the prover
+    let issues = validate(&proving_document);
+    if !issues.is_empty() {
+        error!(
+            "Proving document {} has {} validation issue(s):",
+            proving_document.productFootprint.id,
+            issues.len()
+        );
+        for issue in &issues {
+            error!("  {}", issue);
+        }
+        if !options.prove_rejections || options.dry_run {
+            metrics::job_failed(FailureKind::Validation);
+            return None;
+        }
+        info!("Proving the rejection of the document");
+    }
+
+    if options.dry_run {
+        let report = dry_run(proving_document, options).await?;
+        metrics::job_succeeded(Job::DryRun);
+        return Some(ProvingResponse::DryRun(report));
+    }
+
+    // Retried and replayed documents are answered from the cache
+    let registry = GuestRegistry::global();
+    let cache_key = proof_cache::global()
+        .filter(|_| issues.is_empty())
+        .zip(registry.select(options.guest_version.as_deref()))
+        .map(|(cache, guest)| {
+            let key = ProofCache::key(
+                &proving_document,
+                guest.image_id,
+                &ImageAllowList::from_env(registry),
+                registry.aggregation().image_id,
+                options.receipt_kind.available(),
+            );
+            (cache, key)
+        });
+    if let Some((cache, key)) = &cache_key {
+        if let Some(product_proof) = cache.get(key).await {
+            info!("Found proof of the document in the cache");
+            let response = ProvingResponse::new(
+                options.output_mode,
+                proving_document.productFootprint,
+                product_proof,
+            )?;
+            metrics::job_succeeded(Job::Proof);
+            return Some(response);
+        }
+    }
+
+    let product_footprint = proving_document.productFootprint.clone();
+    match main_proving_logic(proving_document, options, None).await? {
+        // Some issues, e.g. a missing distance, are not rejected by the guest
+        ProvingOutcome::Proven(_) if !issues.is_empty() => {
+            error!("Guest accepted a document with validation issues");
+            metrics::job_failed(FailureKind::Validation);
+            None
+        }
+        ProvingOutcome::Proven(product_proof) => {
+            if let Some((cache, key)) = &cache_key {
+                cache.put(key, &product_proof).await;
+            }
+            let response =
+                ProvingResponse::new(options.output_mode, product_footprint, product_proof)?;
+            metrics::job_succeeded(Job::Proof);
+            Some(response)
+        }
+        ProvingOutcome::Rejected(rejection) => {
+            metrics::job_succeeded(Job::Rejection);
+            Some(ProvingResponse::Rejected(rejection))
+        }
+        ProvingOutcome::Failed(failure) => Some(ProvingResponse::Failed(failure)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proving_service_core::calculation::{preview_pcf, CalculationError};
+    use risc0_zkvm::sha::Digest;
+    use serde_json::json;
+
+    #[test]
+    fn records_the_kind_of_the_receipt_bonsai_returned() {
+        // Bonsai answers composite requests with a succinct receipt
+        let receipt: Receipt = serde_json::from_value(json!({
+            "inner": { "Succinct": {
+                "seal": [],
+                "control_id": Digest::ZERO,
+                "claim": { "Pruned": Digest::ZERO },
+                "hashfn": "poseidon2",
+                "verifier_parameters": Digest::ZERO,
+                "control_inclusion_proof": { "index": 0, "digests": [] }
+            } },
+            "journal": { "bytes": [] },
+            "metadata": { "verifier_parameters": Digest::ZERO }
+        }))
+        .unwrap();
+        let guest = GuestRegistry::global().latest();
+        let stored_receipt = StoredReceipt {
+            proof_receipt: String::new(),
+            receipt_uri: None,
+            receipt_digest: String::new(),
+        };
+
+        let proof = product_proof(
+            "pf-1".to_string(),
+            guest,
+            &receipt,
+            1.0,
+            stored_receipt,
+            None,
+        );
+        assert_eq!(proof.receiptKind.as_deref(), Some("succinct"));
+        assert_eq!(proof.guestVersion.as_deref(), Some(guest.version));
+    }
+
+    #[tokio::test]
+    async fn validation_reports_every_issue() -> Result<(), Box<dyn std::error::Error>> {
+        let json_content = std::fs::read_to_string("json-examples/test_3_1_0.json")?;
+        let mut proving_document = parse_proving_document(&json_content)
+            .await
+            .expect("Failed to parse proving document");
+        assert!(validate(&proving_document).is_empty());
+
+        proving_document.tocData[0].co2eIntensityWTW = "unknown".to_string();
+        proving_document.hocData.clear();
+        let issues = validate(&proving_document);
+        // One unparsable intensity plus two TCEs referencing a missing HOC
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validation_checks_toc_and_hoc_references() -> Result<(), Box<dyn std::error::Error>> {
+        let json_content = std::fs::read_to_string("json-examples/test_3_1_0.json")?;
+        let mut proving_document = parse_proving_document(&json_content)
+            .await
+            .expect("Failed to parse proving document");
+
+        let extensions = &mut proving_document.productFootprint.extensions;
+        extensions.push(extensions[0].clone());
+        extensions[0].data.tces[1].tocId = Some("unknown-toc".to_string());
+        extensions[1].data.tces[0].hocId = Some("unknown-hoc".to_string());
+        let issues = validate(&proving_document);
+
+        let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/productFootprint/extensions/0/data/tces/1/tocId",
+                "/productFootprint/extensions/1/data/tces/0/hocId",
+            ]
+        );
+        assert_eq!(issues[0].message, "TOC 'unknown-toc' is not in tocData");
+        assert_eq!(issues[1].message, "HOC 'unknown-hoc' is not in hocData");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validation_rejects_impossible_values() -> Result<(), Box<dyn std::error::Error>> {
+        let json_content = std::fs::read_to_string("json-examples/test_3_1_0.json")?;
+        let mut proving_document = parse_proving_document(&json_content)
+            .await
+            .expect("Failed to parse proving document");
+
+        let tces = &mut proving_document.productFootprint.extensions[0].data.tces;
+        tces[0].mass = f64::NAN;
+        tces[1].mass = -1.0;
+        proving_document.tocData[0].co2eIntensityWTW = "1e9 gCO2e/tkm".to_string();
+        let issues = validate(&proving_document);
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        assert!(issues[1].path.ends_with("/tces/0/mass"), "{:?}", issues);
+        assert!(matches!(
+            preview_pcf(&proving_document),
+            Err(CalculationError::InvalidValue { field: "mass", .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Write};
+
+use tracing::field::Empty;
+use tracing::{info, info_span, Span};
+use tracing_subscriber::EnvFilter;
+
+/// Installs the `tracing` subscriber. `RUST_LOG` sets the filter (default
+/// `info`), `LOG_FORMAT=json` switches to JSON lines including span fields.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber.json().init(),
+        _ => subscriber.init(),
+    }
+}
+
+/// Span covering one job. The document fields are recorded once the payload
+/// has been parsed, see [`record_document`].
+pub fn job_span(correlation_id: &str) -> Span {
+    info_span!(
+        "job",
+        correlation_id = %correlation_id,
+        footprint_id = Empty,
+        shipment_id = Empty,
+    )
+}
+
+/// Records the footprint and shipment ID on the current job span.
+pub fn record_document(footprint_id: &str, shipment_id: Option<&str>) {
+    let span = Span::current();
+    span.record("footprint_id", footprint_id);
+    if let Some(shipment_id) = shipment_id {
+        span.record("shipment_id", shipment_id);
+    }
+}
+
+/// Guest stderr sink that emits every line as a `guest` event inside the span
+/// the writer was created in, so guest logs of concurrent jobs stay apart.
+pub struct GuestLogWriter {
+    span: Span,
+    buffer: Vec<u8>,
+}
+
+impl Default for GuestLogWriter {
+    fn default() -> Self {
+        GuestLogWriter {
+            span: Span::current(),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl GuestLogWriter {
+    pub fn new() -> Self {
+        GuestLogWriter::default()
+    }
+
+    fn emit(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end();
+        if !line.is_empty() {
+            let _entered = self.span.enter();
+            info!(target: "guest", "{}", line);
+        }
+    }
+}
+
+impl Write for GuestLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.emit(&line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let line = std::mem::take(&mut self.buffer);
+        self.emit(&line);
+        Ok(())
+    }
+}
+
+impl Drop for GuestLogWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
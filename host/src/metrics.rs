@@ -0,0 +1,227 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use prometheus::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge_vec, Encoder, Histogram, HistogramVec, IntCounter,
+    IntCounterVec, IntGaugeVec, TextEncoder,
+};
+
+use crate::prover_backend::CycleStats;
+
+/// Why a job did not produce a result, used as `kind` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    InvalidDocument,
+    Validation,
+    UnknownGuest,
+    UpstreamProof,
+    Execution,
+    Prover,
+    Timeout,
+    CycleLimit,
+    Verification,
+    ReceiptStore,
+}
+
+impl FailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::InvalidDocument => "invalid_document",
+            FailureKind::Validation => "validation",
+            FailureKind::UnknownGuest => "unknown_guest",
+            FailureKind::UpstreamProof => "upstream_proof",
+            FailureKind::Execution => "execution",
+            FailureKind::Prover => "prover",
+            FailureKind::Timeout => "timeout",
+            FailureKind::CycleLimit => "cycle_limit",
+            FailureKind::Verification => "verification",
+            FailureKind::ReceiptStore => "receipt_store",
+        }
+    }
+}
+
+/// Kind of job, used as `job` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    Proof,
+    Aggregation,
+    DryRun,
+    /// A document the guest rejected with a proof of the reason.
+    Rejection,
+}
+
+impl Job {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Job::Proof => "proof",
+            Job::Aggregation => "aggregation",
+            Job::DryRun => "dry_run",
+            Job::Rejection => "rejection",
+        }
+    }
+}
+
+static MESSAGES_CONSUMED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "proving_messages_consumed_total",
+        "Kafka messages consumed from the input topic"
+    )
+    .expect("Failed to register metric")
+});
+
+static JOBS_SUCCEEDED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proving_jobs_succeeded_total",
+        "Jobs that produced a result",
+        &["job"]
+    )
+    .expect("Failed to register metric")
+});
+
+static JOBS_FAILED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proving_jobs_failed_total",
+        "Jobs that failed, by error kind",
+        &["kind"]
+    )
+    .expect("Failed to register metric")
+});
+
+static PROVING_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "proving_duration_seconds",
+        "Wall-clock time of a single prover run",
+        &["job"],
+        exponential_buckets(0.5, 2.0, 14).expect("Invalid buckets")
+    )
+    .expect("Failed to register metric")
+});
+
+static BACKEND_RUNS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proving_backend_runs_total",
+        "Prover runs by backend and outcome",
+        &["backend", "outcome"]
+    )
+    .expect("Failed to register metric")
+});
+
+static BACKEND_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "proving_backend_duration_seconds",
+        "Wall-clock time of a prover run by backend, including uploads and polling",
+        &["backend"],
+        exponential_buckets(0.5, 2.0, 14).expect("Invalid buckets")
+    )
+    .expect("Failed to register metric")
+});
+
+static BACKEND_RETRIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proving_backend_retries_total",
+        "Retried requests to a remote prover",
+        &["backend"]
+    )
+    .expect("Failed to register metric")
+});
+
+static PROOF_CACHE_LOOKUPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proving_proof_cache_lookups_total",
+        "Proof cache lookups by result",
+        &["result"]
+    )
+    .expect("Failed to register metric")
+});
+
+static CYCLES: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "proving_cycles",
+        "Cycles of a prover run from its session stats",
+        &["type"],
+        exponential_buckets(65536.0, 2.0, 14).expect("Invalid buckets")
+    )
+    .expect("Failed to register metric")
+});
+
+static RECEIPT_BYTES: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "proving_receipt_size_bytes",
+        "Size of bincode serialized receipts",
+        exponential_buckets(1024.0, 4.0, 10).expect("Invalid buckets")
+    )
+    .expect("Failed to register metric")
+});
+
+static CONSUMER_LAG: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "proving_kafka_consumer_lag",
+        "Messages waiting in the input topic behind the last consumed one",
+        &["partition"]
+    )
+    .expect("Failed to register metric")
+});
+
+pub fn message_consumed() {
+    MESSAGES_CONSUMED.inc();
+}
+
+pub fn job_succeeded(job: Job) {
+    JOBS_SUCCEEDED.with_label_values(&[job.as_str()]).inc();
+}
+
+pub fn job_failed(kind: FailureKind) {
+    JOBS_FAILED.with_label_values(&[kind.as_str()]).inc();
+}
+
+pub fn observe_proving(job: Job, duration: Duration, stats: &CycleStats) {
+    PROVING_SECONDS
+        .with_label_values(&[job.as_str()])
+        .observe(duration.as_secs_f64());
+    CYCLES
+        .with_label_values(&["user"])
+        .observe(stats.user_cycles as f64);
+    CYCLES
+        .with_label_values(&["paging"])
+        .observe(stats.paging_cycles as f64);
+    CYCLES
+        .with_label_values(&["total"])
+        .observe(stats.total_cycles as f64);
+}
+
+pub fn observe_backend_run(backend: &str, duration: Duration, succeeded: bool) {
+    let outcome = if succeeded { "succeeded" } else { "failed" };
+    BACKEND_RUNS.with_label_values(&[backend, outcome]).inc();
+    BACKEND_SECONDS
+        .with_label_values(&[backend])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn backend_request_retried(backend: &str) {
+    BACKEND_RETRIES.with_label_values(&[backend]).inc();
+}
+
+pub fn proof_cache_lookup(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    PROOF_CACHE_LOOKUPS.with_label_values(&[result]).inc();
+}
+
+pub fn observe_receipt_size(bytes: usize) {
+    RECEIPT_BYTES.observe(bytes as f64);
+}
+
+pub fn set_consumer_lag(partition: i32, lag: i64) {
+    CONSUMER_LAG
+        .with_label_values(&[&partition.to_string()])
+        .set(lag.max(0));
+}
+
+/// All registered metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("Failed to encode metrics");
+    String::from_utf8(buffer).expect("Metrics are not valid UTF-8")
+}
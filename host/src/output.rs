@@ -0,0 +1,284 @@
+use proving_service_core::product_footprint::{FootprintExtension, ProductFootprint, ProductProof};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::{error, warn};
+
+use crate::dry_run::DryRunReport;
+use crate::metrics::{self, FailureKind};
+
+/// `dataSchema` of the proof extension appended to completed footprints.
+pub const PROOF_EXTENSION_SCHEMA: &str =
+    "https://raw.githubusercontent.com/ACP-PCVCF/proving-service/main/schemas/proof-extension.schema.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Only the bare `ProductProof` (default).
+    #[default]
+    ProductProof,
+    /// The input `ProductFootprint` completed with the PCF and a proof extension.
+    ProductFootprint,
+}
+
+impl OutputMode {
+    /// Reads `OUTPUT_MODE` (`proof` or `footprint`), defaulting to `proof`.
+    pub fn from_env() -> Self {
+        std::env::var("OUTPUT_MODE")
+            .map(|mode| Self::parse(&mode))
+            .unwrap_or_default()
+    }
+
+    pub fn from_name(mode: &str) -> Option<Self> {
+        match mode {
+            "footprint" => Some(OutputMode::ProductFootprint),
+            "proof" => Some(OutputMode::ProductProof),
+            _ => None,
+        }
+    }
+
+    /// Parses an output mode, falling back to `proof` for unknown values.
+    pub fn parse(mode: &str) -> Self {
+        Self::from_name(mode).unwrap_or_else(|| {
+            warn!("Unknown output mode '{}', falling back to 'proof'", mode);
+            OutputMode::ProductProof
+        })
+    }
+}
+
+/// A document the guest rejected, with the receipt proving why.
+///
+/// The receipt halted with `REJECTED_EXIT_CODE`, so it cannot be used as an
+/// upstream proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenRejection {
+    pub product_footprint_id: String,
+    /// `CalculationError` code, e.g. `unknownToc`.
+    pub code: String,
+    pub tce_id: Option<String>,
+    pub message: String,
+    pub proof_receipt: String,
+    pub receipt_uri: Option<String>,
+    pub receipt_digest: String,
+    pub image_id: String,
+    pub guest_version: String,
+}
+
+/// A proving job that was aborted at one of its limits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvingFailure {
+    pub product_footprint_id: String,
+    /// `FailureKind` label, `timeout` or `cycle_limit`.
+    pub kind: String,
+    pub message: String,
+}
+
+/// What proving a document produced.
+#[derive(Debug, Clone)]
+pub enum ProvingOutcome {
+    Proven(ProductProof),
+    Rejected(ProvenRejection),
+    Failed(ProvingFailure),
+}
+
+/// Result of a message. JSON carries the bare result, e.g. a `ProductProof`.
+/// Binary formats like postcard carry no field names to tell the results apart,
+/// so there the variant is tagged.
+#[derive(Debug)]
+pub enum ProvingResponse {
+    Proof(ProductProof),
+    Footprint(ProductFootprint<FootprintExtension>),
+    DryRun(DryRunReport),
+    Rejected(ProvenRejection),
+    Failed(ProvingFailure),
+}
+
+impl Serialize for ProvingResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        const NAME: &str = "ProvingResponse";
+        match (self, serializer.is_human_readable()) {
+            (ProvingResponse::Proof(proof), true) => proof.serialize(serializer),
+            (ProvingResponse::Footprint(footprint), true) => footprint.serialize(serializer),
+            (ProvingResponse::DryRun(report), true) => report.serialize(serializer),
+            (ProvingResponse::Rejected(rejection), true) => rejection.serialize(serializer),
+            (ProvingResponse::Failed(failure), true) => failure.serialize(serializer),
+            (ProvingResponse::Proof(proof), false) => {
+                serializer.serialize_newtype_variant(NAME, 0, "Proof", proof)
+            }
+            (ProvingResponse::Footprint(footprint), false) => {
+                serializer.serialize_newtype_variant(NAME, 1, "Footprint", footprint)
+            }
+            (ProvingResponse::DryRun(report), false) => {
+                serializer.serialize_newtype_variant(NAME, 2, "DryRun", report)
+            }
+            (ProvingResponse::Rejected(rejection), false) => {
+                serializer.serialize_newtype_variant(NAME, 3, "Rejected", rejection)
+            }
+            (ProvingResponse::Failed(failure), false) => {
+                serializer.serialize_newtype_variant(NAME, 4, "Failed", failure)
+            }
+        }
+    }
+}
+
+/// Decodes the tagged results of binary formats.
+impl<'de> Deserialize<'de> for ProvingResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "ProvingResponse")]
+        enum Tagged {
+            Proof(ProductProof),
+            Footprint(ProductFootprint<FootprintExtension>),
+            DryRun(DryRunReport),
+            Rejected(ProvenRejection),
+            Failed(ProvingFailure),
+        }
+
+        if deserializer.is_human_readable() {
+            return Err(D::Error::custom(
+                "JSON results are untagged, decode the expected result type instead",
+            ));
+        }
+        Ok(match Tagged::deserialize(deserializer)? {
+            Tagged::Proof(proof) => ProvingResponse::Proof(proof),
+            Tagged::Footprint(footprint) => ProvingResponse::Footprint(footprint),
+            Tagged::DryRun(report) => ProvingResponse::DryRun(report),
+            Tagged::Rejected(rejection) => ProvingResponse::Rejected(rejection),
+            Tagged::Failed(failure) => ProvingResponse::Failed(failure),
+        })
+    }
+}
+
+impl ProvingResponse {
+    /// Returns `None` if the footprint cannot be completed with the proof.
+    pub fn new(mode: OutputMode, footprint: ProductFootprint, proof: ProductProof) -> Option<Self> {
+        match mode {
+            OutputMode::ProductProof => Some(ProvingResponse::Proof(proof)),
+            OutputMode::ProductFootprint => {
+                match footprint.into_proven(proof, PROOF_EXTENSION_SCHEMA) {
+                    Ok(footprint) => Some(ProvingResponse::Footprint(footprint)),
+                    Err(e) => {
+                        error!("Failed to complete the footprint: {}", e);
+                        metrics::job_failed(FailureKind::Validation);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dry_run::SegmentCycles;
+    use crate::wire_format::WireFormat;
+    use proving_service_core::journal::Rejection;
+    use serde_json::json;
+
+    fn proof() -> ProductProof {
+        serde_json::from_value(json!({
+            "productFootprintId": "pf-1",
+            "proofReceipt": "",
+            "pcf": 12.5,
+            "proofReference": "123",
+            "imageId": "00",
+            "guestVersion": "0.9.0",
+            "receiptKind": "succinct"
+        }))
+        .unwrap()
+    }
+
+    fn footprint() -> ProductFootprint {
+        serde_json::from_value(json!({
+            "id": "pf-1",
+            "created": "2025-01-01T00:00:00Z",
+            "companyName": "Carrier",
+            "companyIds": [],
+            "productDescription": "Transport",
+            "productIds": [],
+            "productCategoryCpc": 6511,
+            "productNameCompany": "Transport",
+            "pcf": null,
+            "extensions": [{
+                "dataSchema": "https://api.ileap.sine.dev/shipment-footprint.json",
+                "data": { "mass": 1000.0, "shipmentId": "shipment-1", "tces": [] }
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn responses() -> Vec<ProvingResponse> {
+        vec![
+            ProvingResponse::Proof(proof()),
+            ProvingResponse::new(OutputMode::ProductFootprint, footprint(), proof()).unwrap(),
+            ProvingResponse::DryRun(DryRunReport {
+                product_footprint_id: "pf-1".to_string(),
+                pcf: 0.0,
+                rejection: Some(Rejection {
+                    code: "unknownToc".to_string(),
+                    tce_id: Some("tce-1".to_string()),
+                    message: "unknown TOC".to_string(),
+                }),
+                guest_version: "0.9.0".to_string(),
+                image_id: "00".to_string(),
+                segments: vec![SegmentCycles {
+                    po2: 20,
+                    user_cycles: 1000,
+                }],
+                user_cycles: 1000,
+                total_cycles: 1 << 20,
+                estimated_proving_seconds: 104.8576,
+            }),
+            ProvingResponse::Rejected(ProvenRejection {
+                product_footprint_id: "pf-1".to_string(),
+                code: "unknownToc".to_string(),
+                tce_id: None,
+                message: "unknown TOC".to_string(),
+                proof_receipt: String::new(),
+                receipt_uri: Some("s3://receipts/00.bin".to_string()),
+                receipt_digest: "00".to_string(),
+                image_id: "00".to_string(),
+                guest_version: "0.9.0".to_string(),
+            }),
+            ProvingResponse::Failed(ProvingFailure {
+                product_footprint_id: "pf-1".to_string(),
+                kind: "timeout".to_string(),
+                message: "proving took longer than 10s".to_string(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn binary_formats_tell_responses_apart() {
+        for format in [WireFormat::Postcard, WireFormat::Cbor] {
+            for response in responses() {
+                let bytes = format.encode(&response).unwrap();
+                let decoded: ProvingResponse = format.decode(&bytes).unwrap();
+                assert_eq!(
+                    std::mem::discriminant(&decoded),
+                    std::mem::discriminant(&response)
+                );
+                assert_eq!(
+                    serde_json::to_value(&decoded).unwrap(),
+                    serde_json::to_value(&response).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn json_responses_stay_untagged() {
+        let json = serde_json::to_value(ProvingResponse::Proof(proof())).unwrap();
+        assert_eq!(json, serde_json::to_value(proof()).unwrap());
+
+        let ProvingResponse::Footprint(footprint) =
+            ProvingResponse::new(OutputMode::ProductFootprint, footprint(), proof()).unwrap()
+        else {
+            panic!("not a footprint");
+        };
+        let json = serde_json::to_value(&footprint).unwrap();
+        assert_eq!(json["extensions"][0]["data"]["shipmentId"], "shipment-1");
+        assert_eq!(json["extensions"][1]["data"]["pcfProofs"][0]["pcf"], 12.5);
+    }
+}
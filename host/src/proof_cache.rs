@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use proving_service_core::hashing::canonical_hash;
+use proving_service_core::product_footprint::ProductProof;
+use proving_service_core::proofing_document::ProofingDocument;
+use risc0_zkvm::sha::Digest;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::allow_list::ImageAllowList;
+use crate::metrics;
+use crate::receipt_kind::ReceiptKind;
+
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Everything a proof depends on: the document and the guest input besides it.
+#[derive(Serialize)]
+struct CacheKey<'a> {
+    document: &'a ProofingDocument,
+    image_id: Digest,
+    allowed_image_ids: &'a [Digest],
+    aggregation_image_id: Digest,
+    receipt_kind: &'a str,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    /// Seconds since the Unix epoch.
+    created_at: u64,
+    proof: ProductProof,
+}
+
+/// Proofs of already proven documents as `<key>.json` files in a local
+/// directory, so retried and replayed messages are answered without proving.
+pub struct ProofCache {
+    root: PathBuf,
+    ttl: Duration,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+impl ProofCache {
+    pub fn new(root: impl Into<PathBuf>, ttl: Duration) -> Self {
+        ProofCache {
+            root: root.into(),
+            ttl,
+        }
+    }
+
+    /// Hex `canonical_hash` of `document` and everything else the proof depends
+    /// on. The document is serialized from its types, so key order, whitespace
+    /// and unknown fields of the message do not matter.
+    pub fn key(
+        document: &ProofingDocument,
+        image_id: Digest,
+        allow_list: &ImageAllowList,
+        aggregation_image_id: Digest,
+        receipt_kind: ReceiptKind,
+    ) -> String {
+        let key = CacheKey {
+            document,
+            image_id,
+            allowed_image_ids: allow_list.ids(),
+            aggregation_image_id,
+            receipt_kind: receipt_kind.as_str(),
+        };
+        hex::encode(canonical_hash(&key).as_bytes())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{}.json", key))
+    }
+
+    /// The cached proof, unless there is none or it is older than the TTL.
+    pub async fn get(&self, key: &str) -> Option<ProductProof> {
+        let proof = self.read(key).await;
+        metrics::proof_cache_lookup(proof.is_some());
+        proof
+    }
+
+    async fn read(&self, key: &str) -> Option<ProductProof> {
+        let path = self.path(key);
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed to read cached proof {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let entry: CacheEntry = match serde_json::from_slice(&bytes) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Ignoring invalid cached proof {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        if now_secs().saturating_sub(entry.created_at) >= self.ttl.as_secs() {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to remove expired proof {}: {}", path.display(), e);
+            }
+            return None;
+        }
+        Some(entry.proof)
+    }
+
+    /// Caches `proof`; failures are logged, as proving already succeeded.
+    pub async fn put(&self, key: &str, proof: &ProductProof) {
+        let entry = CacheEntry {
+            created_at: now_secs(),
+            proof: proof.clone(),
+        };
+        let path = self.path(key);
+        // Written to a temporary file first, so readers never see a partial entry
+        let tmp_path = self.root.join(format!("{}.json.tmp", key));
+        let result = async {
+            let json = serde_json::to_vec(&entry).map_err(std::io::Error::other)?;
+            tokio::fs::create_dir_all(&self.root).await?;
+            tokio::fs::write(&tmp_path, json).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to cache proof {}: {}", path.display(), e);
+        }
+    }
+
+    /// Removes the entries, and left-over temporary files, written longer than
+    /// the TTL ago. Expired entries are otherwise only removed when looked up.
+    pub async fn sweep(&self) {
+        let mut entries = match tokio::fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Failed to sweep proof cache {}: {}", self.root.display(), e);
+                return;
+            }
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let age = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| modified.elapsed().unwrap_or_default());
+            match age {
+                Ok(age) if age >= self.ttl => match tokio::fs::remove_file(&path).await {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!("Failed to remove expired proof {}: {}", path.display(), e),
+                },
+                Ok(_) => {}
+                Err(e) => warn!("Failed to check age of {}: {}", path.display(), e),
+            }
+        }
+        if removed > 0 {
+            info!("Removed {} expired proofs from the cache", removed);
+        }
+    }
+
+    /// Sweeps the cache every hour.
+    pub async fn sweep_periodically(&self) {
+        loop {
+            self.sweep().await;
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+        }
+    }
+}
+
+static CACHE: LazyLock<Option<ProofCache>> = LazyLock::new(from_env);
+
+/// Reads `PROOF_CACHE` (`none` or `disk`), `PROOF_CACHE_PATH` and
+/// `PROOF_CACHE_TTL_SECS` (default one week).
+fn from_env() -> Option<ProofCache> {
+    let env =
+        |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+    match env("PROOF_CACHE", "none").as_str() {
+        "disk" => {
+            let ttl = env("PROOF_CACHE_TTL_SECS", "")
+                .parse()
+                .unwrap_or(DEFAULT_TTL_SECS);
+            let root = env("PROOF_CACHE_PATH", "proof-cache");
+            info!("Caching proofs in {} for {}s", root, ttl);
+            Some(ProofCache::new(root, Duration::from_secs(ttl)))
+        }
+        "" | "none" => None,
+        other => {
+            warn!("Unknown PROOF_CACHE '{}', proofs are not cached", other);
+            None
+        }
+    }
+}
+
+pub fn global() -> Option<&'static ProofCache> {
+    CACHE.as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn proof_cache_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let json_content = fs::read_to_string("json-examples/test_3_1_0.json")?;
+        let document: ProofingDocument = serde_json::from_str(&json_content)?;
+        let allow_list = ImageAllowList::new([Digest::ZERO]);
+        let key_of = |document: &ProofingDocument, allow_list: &ImageAllowList, kind| {
+            ProofCache::key(document, Digest::ZERO, allow_list, Digest::ZERO, kind)
+        };
+        // Whitespace and key order of the message do not change the key
+        let value: serde_json::Value = serde_json::from_str(&json_content)?;
+        let reordered: ProofingDocument = serde_json::from_str(&value.to_string())?;
+        let key = key_of(&document, &allow_list, ReceiptKind::Succinct);
+        assert_eq!(key, key_of(&reordered, &allow_list, ReceiptKind::Succinct));
+        assert_ne!(key, key_of(&document, &allow_list, ReceiptKind::Composite));
+        // Proofs composed under another allow-list are not reused
+        let extended = ImageAllowList::new([Digest::ZERO, Digest::from([1u8; 32])]);
+        assert_ne!(key, key_of(&document, &extended, ReceiptKind::Succinct));
+
+        let root = std::env::temp_dir().join(format!("proof-cache-{}", std::process::id()));
+        let proof = ProductProof {
+            productFootprintId: document.productFootprint.id.clone(),
+            proofReceipt: "receipt".to_string(),
+            pcf: 42.0,
+            proofReference: "123".to_string(),
+            imageId: hex::encode(Digest::ZERO.as_bytes()),
+            guestVersion: None,
+            receiptKind: None,
+            receiptUri: None,
+            receiptDigest: None,
+            disclosureProofs: None,
+        };
+
+        let cache = ProofCache::new(&root, Duration::from_secs(60));
+        assert!(cache.get(&key).await.is_none());
+        cache.put(&key, &proof).await;
+        let cached = cache.get(&key).await.expect("proof is cached");
+        assert_eq!(cached.pcf, proof.pcf);
+        assert_eq!(cached.proofReceipt, proof.proofReceipt);
+
+        // Expired entries are dropped
+        let expired = ProofCache::new(&root, Duration::ZERO);
+        assert!(expired.get(&key).await.is_none());
+        assert!(cache.get(&key).await.is_none());
+
+        // and swept without being looked up
+        cache.put(&key, &proof).await;
+        cache.sweep().await;
+        assert!(root.join(format!("{}.json", key)).exists());
+        expired.sweep().await;
+        assert_eq!(fs::read_dir(&root)?.count(), 0);
+
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1074 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use risc0_zkvm::sha::Digest;
+use risc0_zkvm::{
+    ExternalProver, InnerAssumptionReceipt, Prover, ProverOpts, Receipt, SessionStats,
+    VerifierContext,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
+
+use crate::env_helper::GuestInput;
+use crate::metrics::{self, FailureKind};
+
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1_000;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled for every further one.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+/// The delay stops growing after this many doublings, at 16 seconds.
+const MAX_BACKOFF_DOUBLINGS: u32 = 6;
+
+/// Cycle counts of a prover run. Remote provers report neither paging nor
+/// reserved cycles, so they stay zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CycleStats {
+    pub segments: usize,
+    pub total_cycles: u64,
+    pub user_cycles: u64,
+    pub paging_cycles: u64,
+    pub reserved_cycles: u64,
+}
+
+impl From<&SessionStats> for CycleStats {
+    fn from(stats: &SessionStats) -> Self {
+        CycleStats {
+            segments: stats.segments,
+            total_cycles: stats.total_cycles,
+            user_cycles: stats.user_cycles,
+            paging_cycles: stats.paging_cycles,
+            reserved_cycles: stats.reserved_cycles,
+        }
+    }
+}
+
+/// Default wall-clock limit, well below the consumer's `max.poll.interval.ms`
+/// of 30 minutes.
+const DEFAULT_TIMEOUT_SECS: u64 = 25 * 60;
+/// Default session cycle limit, 64 segments of 2^20 cycles. A local prover
+/// cannot be interrupted, so this bounds how long it runs after a timeout.
+pub const DEFAULT_CYCLE_LIMIT: u64 = 1 << 26;
+
+/// Bounds of a single prover run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvingLimits {
+    /// Session cycle limit of the guest, unlimited if `None`.
+    pub cycles: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ProvingLimits {
+    fn default() -> Self {
+        ProvingLimits {
+            cycles: Some(DEFAULT_CYCLE_LIMIT),
+            timeout: Some(Duration::from_secs(DEFAULT_TIMEOUT_SECS)),
+        }
+    }
+}
+
+impl ProvingLimits {
+    /// Reads `PROVING_CYCLE_LIMIT` and `PROVING_TIMEOUT_SECS`, where 0 disables
+    /// the limit.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        ProvingLimits {
+            cycles: match var("PROVING_CYCLE_LIMIT") {
+                Some(0) => None,
+                Some(cycles) => Some(cycles),
+                None => ProvingLimits::default().cycles,
+            },
+            timeout: match var("PROVING_TIMEOUT_SECS") {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => ProvingLimits::default().timeout,
+            },
+        }
+    }
+
+    /// Applies a lower cycle limit, e.g. from a message header.
+    pub fn tighten_cycles(&mut self, cycles: u64) {
+        self.cycles = Some(self.cycles.map_or(cycles, |limit| limit.min(cycles)));
+    }
+
+    /// Applies a shorter timeout, e.g. from a message header.
+    pub fn tighten_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(self.timeout.map_or(timeout, |limit| limit.min(timeout)));
+    }
+}
+
+pub struct ProvingRun {
+    pub receipt: Receipt,
+    pub stats: CycleStats,
+}
+
+/// Where guests are proven.
+pub enum ProverBackend {
+    /// In-process CPU prover, requires the `prove` feature.
+    Local,
+    /// `r0vm` child process per proof.
+    R0vm { path: PathBuf },
+    /// Bonsai compatible REST API.
+    Bonsai(BonsaiClient),
+}
+
+static BACKEND: LazyLock<Option<ProverBackend>> =
+    LazyLock::new(|| match ProverBackend::from_env() {
+        Ok(backend) => {
+            info!("Prover backend: {}", backend.name());
+            Some(backend)
+        }
+        Err(e) => {
+            error!("Failed to set up the prover backend: {}", e);
+            None
+        }
+    });
+
+pub fn global() -> Option<&'static ProverBackend> {
+    BACKEND.as_ref()
+}
+
+/// Held by the thread of a local or `r0vm` prover until it returns, also after
+/// its job was abandoned.
+static PROVER_THREAD: LazyLock<Arc<Semaphore>> = LazyLock::new(|| Arc::new(Semaphore::new(1)));
+
+/// Waits until no prover thread runs anymore, so the consumer does not take the
+/// next job while a local prover abandoned after a timeout still runs.
+pub async fn wait_until_idle() {
+    if PROVER_THREAD.available_permits() > 0 {
+        return;
+    }
+    warn!("Waiting for the abandoned prover to finish before taking the next job");
+    let _ = PROVER_THREAD.acquire().await;
+}
+
+impl ProverBackend {
+    /// Reads `PROVER_BACKEND` (`local`, `r0vm` or `bonsai`). If unset, Bonsai is
+    /// used when `BONSAI_API_URL` and `BONSAI_API_KEY` are set outside of dev
+    /// mode, like `default_prover` does, otherwise the local prover if built in
+    /// and `r0vm` if not.
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let backend = var("PROVER_BACKEND").unwrap_or_else(|| {
+            if var("BONSAI_API_URL").is_some()
+                && var("BONSAI_API_KEY").is_some()
+                && !ProverOpts::default().dev_mode()
+            {
+                "bonsai".to_string()
+            } else if cfg!(feature = "prove") {
+                "local".to_string()
+            } else {
+                "r0vm".to_string()
+            }
+        });
+
+        match backend.as_str() {
+            "local" if cfg!(feature = "prove") => Ok(ProverBackend::Local),
+            "local" => Err("the host was built without the 'prove' feature".to_string()),
+            "r0vm" => Ok(ProverBackend::R0vm {
+                path: PathBuf::from(var("RISC0_SERVER_PATH").unwrap_or_else(|| "r0vm".to_string())),
+            }),
+            "bonsai" => {
+                let url = var("BONSAI_API_URL").ok_or("BONSAI_API_URL is not set")?;
+                let api_key = var("BONSAI_API_KEY").ok_or("BONSAI_API_KEY is not set")?;
+                let millis = |name: &str, default: u64| match var(name) {
+                    Some(value) => value
+                        .parse()
+                        .map_err(|_| format!("{}: '{}' is not a number", name, value)),
+                    None => Ok(default),
+                };
+                let max_retries = match var("BONSAI_MAX_RETRIES") {
+                    Some(value) => value
+                        .parse()
+                        .map_err(|_| format!("BONSAI_MAX_RETRIES: '{}' is not a number", value))?,
+                    None => DEFAULT_MAX_RETRIES,
+                };
+                let client = BonsaiClient::new(
+                    &url,
+                    &api_key,
+                    Duration::from_millis(millis("BONSAI_TIMEOUT_MS", DEFAULT_REQUEST_TIMEOUT_MS)?),
+                )?
+                .with_poll_interval(Duration::from_millis(millis(
+                    "BONSAI_POLL_INTERVAL_MS",
+                    DEFAULT_POLL_INTERVAL_MS,
+                )?))
+                .with_max_retries(max_retries);
+                Ok(ProverBackend::Bonsai(client))
+            }
+            other => Err(format!("unknown prover backend '{}'", other)),
+        }
+    }
+
+    /// Name of the backend, used as `backend` label.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProverBackend::Local => "local",
+            ProverBackend::R0vm { .. } => "r0vm",
+            ProverBackend::Bonsai(_) => "bonsai",
+        }
+    }
+
+    /// Checks that `r0vm` can be run or that a remote prover answers; the local
+    /// prover is always available.
+    pub async fn check(&self) -> Result<(), String> {
+        match self {
+            ProverBackend::Local => Ok(()),
+            ProverBackend::R0vm { path } => {
+                let status = tokio::process::Command::new(path)
+                    .arg("--version")
+                    .output()
+                    .await
+                    .map_err(|e| format!("{}: {}", path.display(), e))?
+                    .status;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{} --version exited with {}",
+                        path.display(),
+                        status
+                    ))
+                }
+            }
+            ProverBackend::Bonsai(client) => client.version().await.map(|_| ()),
+        }
+    }
+
+    /// Proves the guest `elf` with `input` and records the run per backend.
+    ///
+    /// After `timeout` the job fails, `r0vm` is killed and a Bonsai session is
+    /// stopped. A local prover cannot be interrupted and keeps running on its
+    /// blocking thread until it finishes or runs into the cycle limit of `input`,
+    /// see [`wait_until_idle`].
+    pub async fn prove(
+        &self,
+        elf: &'static [u8],
+        image_id: Digest,
+        input: &GuestInput,
+        opts: &ProverOpts,
+        timeout: Option<Duration>,
+    ) -> Result<ProvingRun, ProverError> {
+        let start_time = Instant::now();
+        let proving = async {
+            let result = match self {
+                ProverBackend::Local => prove_on_blocking_thread(None, elf, input, opts).await,
+                ProverBackend::R0vm { path } => {
+                    prove_on_blocking_thread(Some(path.clone()), elf, input, opts).await
+                }
+                ProverBackend::Bonsai(client) => client.prove(elf, image_id, input, opts).await,
+            };
+            result.map_err(|message| ProverError::classify(message, input.session_limit()))
+        };
+        let result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, proving).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.abandon();
+                    Err(ProverError::Timeout(timeout))
+                }
+            },
+            None => proving.await,
+        };
+        metrics::observe_backend_run(self.name(), start_time.elapsed(), result.is_ok());
+        result
+    }
+
+    /// Stops what is left of a run whose future was dropped.
+    fn abandon(&self) {
+        match self {
+            ProverBackend::Local => {
+                warn!("The local prover keeps running until it finishes or hits the cycle limit")
+            }
+            ProverBackend::R0vm { path } => kill_r0vm(path),
+            // Stopped by the `RunningSession` of the dropped future
+            ProverBackend::Bonsai(_) => {}
+        }
+    }
+}
+
+/// Kills the `r0vm` servers this process started from `path`. `ExternalProver`
+/// does not expose its child, so they are looked up by their parent in `/proc`.
+/// Once killed, the prover thread fails and returns.
+fn kill_r0vm(path: &Path) {
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to look up r0vm processes: {}", e);
+            return;
+        }
+    };
+    let parent = std::process::id().to_string();
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|pid| pid.parse().ok()) else {
+            continue;
+        };
+        // `<pid> (<comm>) <state> <ppid> ...`, where comm may contain spaces
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let ppid = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split(' ').nth(2));
+        if ppid != Some(parent.as_str()) {
+            continue;
+        }
+        let Ok(cmdline) = std::fs::read(entry.path().join("cmdline")) else {
+            continue;
+        };
+        let args: Vec<&[u8]> = cmdline.split(|byte| *byte == 0).collect();
+        let is_server = args.contains(&b"--port".as_slice())
+            && args.contains(&path.as_os_str().as_encoded_bytes());
+        if !is_server {
+            continue;
+        }
+        warn!("Killing r0vm process {}", pid);
+        // SAFETY: `kill` only sends a signal, `pid` is a child of this process
+        if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+            error!(
+                "Failed to kill r0vm process {}: {}",
+                pid,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Why a prover run did not produce a receipt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProverError {
+    /// The run did not finish within the wall-clock limit.
+    Timeout(Duration),
+    /// The guest exceeded the session cycle limit.
+    CycleLimit(u64),
+    Failed(String),
+}
+
+impl ProverError {
+    fn classify(message: String, session_limit: Option<u64>) -> Self {
+        match session_limit {
+            Some(limit) if message.contains("Session limit exceeded") => {
+                ProverError::CycleLimit(limit)
+            }
+            _ => ProverError::Failed(message),
+        }
+    }
+
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            ProverError::Timeout(_) => FailureKind::Timeout,
+            ProverError::CycleLimit(_) => FailureKind::CycleLimit,
+            ProverError::Failed(_) => FailureKind::Prover,
+        }
+    }
+}
+
+impl fmt::Display for ProverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverError::Timeout(timeout) => {
+                write!(f, "proving timed out after {}s", timeout.as_secs())
+            }
+            ProverError::CycleLimit(limit) => {
+                write!(f, "guest exceeded the limit of {} cycles", limit)
+            }
+            ProverError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[cfg(feature = "prove")]
+fn local_prover() -> Rc<dyn Prover> {
+    Rc::new(risc0_zkvm::LocalProver::new("local"))
+}
+
+#[cfg(not(feature = "prove"))]
+fn local_prover() -> Rc<dyn Prover> {
+    unreachable!("the local prover backend requires the 'prove' feature")
+}
+
+/// Proves with the local prover, or with `r0vm` at `r0vm_path`, without
+/// blocking the runtime. Provers are not `Send`, so it is created on the thread.
+async fn prove_on_blocking_thread(
+    r0vm_path: Option<PathBuf>,
+    elf: &'static [u8],
+    input: &GuestInput,
+    opts: &ProverOpts,
+) -> Result<ProvingRun, String> {
+    let input = input.clone();
+    let opts = opts.clone();
+    let permit = PROVER_THREAD
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || {
+        // Released when the prover returns, even if its job was abandoned
+        let _permit = permit;
+        let prover: Rc<dyn Prover> = match r0vm_path {
+            Some(path) => Rc::new(ExternalProver::new("r0vm", path)),
+            None => local_prover(),
+        };
+        let info = prover
+            .prove_with_opts(input.executor_env(), elf, &opts)
+            .map_err(|e| e.to_string())?;
+        Ok(ProvingRun {
+            stats: CycleStats::from(&info.stats),
+            receipt: info.receipt,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Deserialize)]
+struct UploadUrl {
+    url: String,
+    #[serde(default)]
+    uuid: String,
+}
+
+#[derive(Serialize)]
+struct ProofRequest<'a> {
+    img: &'a str,
+    input: &'a str,
+    assumptions: &'a [String],
+    execute_only: bool,
+    exec_cycle_limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct SnarkRequest<'a> {
+    session_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct CreatedSession {
+    uuid: String,
+}
+
+#[derive(Deserialize)]
+struct BonsaiStats {
+    segments: usize,
+    total_cycles: u64,
+    cycles: u64,
+}
+
+/// Status of a proving session, or with `output` of a SNARK session.
+#[derive(Deserialize)]
+struct SessionStatus {
+    status: String,
+    receipt_url: Option<String>,
+    output: Option<String>,
+    error_msg: Option<String>,
+    stats: Option<BonsaiStats>,
+}
+
+/// Client of the Bonsai REST API. Every request has a timeout and is retried
+/// with exponential backoff on connection errors, 429 and 5xx responses.
+/// `POST` requests create sessions, which are billed, so they are only retried
+/// if Bonsai cannot have started one: on connection errors, 429 and 503.
+pub struct BonsaiClient {
+    url: String,
+    client: reqwest::Client,
+    poll_interval: Duration,
+    max_retries: u32,
+}
+
+impl BonsaiClient {
+    pub fn new(url: &str, api_key: &str, request_timeout: Duration) -> Result<Self, String> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        let header =
+            |value: &str| reqwest::header::HeaderValue::from_str(value).map_err(|e| e.to_string());
+        headers.insert("x-api-key", header(api_key)?);
+        headers.insert("x-risc0-version", header(risc0_zkvm::VERSION)?);
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(request_timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(BonsaiClient {
+            url: url.trim_end_matches('/').to_string(),
+            client,
+            poll_interval: Duration::from_millis(DEFAULT_POLL_INTERVAL_MS),
+            max_retries: DEFAULT_MAX_RETRIES,
+        })
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/{}", self.url, path)
+    }
+
+    /// Sends the request built by `request`, retrying transient failures.
+    async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response, String> {
+        let mut attempt = 0;
+        loop {
+            let request = request().build().map_err(|e| e.to_string())?;
+            let idempotent = request.method() != Method::POST;
+            let error = match self.client.execute(request).await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    let error = format!("Bonsai responded with {}: {}", status, body);
+                    let transient = match status {
+                        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+                        _ => idempotent && status.is_server_error(),
+                    };
+                    if !transient {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(e) if e.is_connect() || (idempotent && e.is_timeout()) => e.to_string(),
+                Err(e) => return Err(e.to_string()),
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            attempt += 1;
+            warn!(
+                "Bonsai request failed ({}), retry {} of {}",
+                error, attempt, self.max_retries
+            );
+            metrics::backend_request_retried("bonsai");
+            let doublings = (attempt - 1).min(MAX_BACKOFF_DOUBLINGS);
+            tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(doublings)).await;
+        }
+    }
+
+    async fn json<T: for<'de> Deserialize<'de>>(response: Response) -> Result<T, String> {
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Bonsai response: {}", e))
+    }
+
+    async fn version(&self) -> Result<String, String> {
+        let response = self
+            .send(|| self.client.get(self.endpoint("version")))
+            .await?;
+        response.text().await.map_err(|e| e.to_string())
+    }
+
+    /// Uploads `bytes` as new `inputs` or `receipts` and returns its UUID.
+    async fn upload(&self, kind: &str, bytes: Vec<u8>) -> Result<String, String> {
+        let path = format!("{}/upload", kind);
+        let upload: UploadUrl =
+            Self::json(self.send(|| self.client.get(self.endpoint(&path))).await?).await?;
+        self.send(|| self.client.put(&upload.url).body(bytes.clone()))
+            .await?;
+        Ok(upload.uuid)
+    }
+
+    async fn upload_image(&self, image_id: &str, elf: &[u8]) -> Result<(), String> {
+        let path = format!("images/upload/{}", image_id);
+        let response = self.send(|| self.client.get(self.endpoint(&path))).await?;
+        // No content if the image is already known
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(());
+        }
+        let upload: UploadUrl = Self::json(response).await?;
+        self.send(|| self.client.put(&upload.url).body(elf.to_vec()))
+            .await?;
+        Ok(())
+    }
+
+    /// Polls `<kind>/status/<uuid>` until the session is no longer running.
+    async fn wait_for(&self, kind: &str, uuid: &str) -> Result<SessionStatus, String> {
+        let path = format!("{}/status/{}", kind, uuid);
+        loop {
+            let status: SessionStatus =
+                Self::json(self.send(|| self.client.get(self.endpoint(&path))).await?).await?;
+            match status.status.as_str() {
+                "RUNNING" => tokio::time::sleep(self.poll_interval).await,
+                "SUCCEEDED" => return Ok(status),
+                other => {
+                    return Err(format!(
+                        "Bonsai session {} ended with {}: {}",
+                        uuid,
+                        other,
+                        status.error_msg.unwrap_or_default()
+                    ))
+                }
+            }
+        }
+    }
+
+    async fn download_receipt(&self, url: &str) -> Result<Receipt, String> {
+        let bytes = self
+            .send(|| self.client.get(url))
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| e.to_string())?;
+        bincode::deserialize(&bytes).map_err(|e| format!("Invalid receipt from Bonsai: {}", e))
+    }
+
+    /// Bonsai proves succinct receipts, wrapped into Groth16 on request.
+    /// Assumptions have to be succinct as well. Like `BonsaiProver`, receipts of
+    /// guests that did not exit successfully are only accepted if
+    /// `opts.prove_guest_errors` is set.
+    pub async fn prove(
+        &self,
+        elf: &[u8],
+        image_id: Digest,
+        input: &GuestInput,
+        opts: &ProverOpts,
+    ) -> Result<ProvingRun, String> {
+        let image_id_hex = hex::encode(image_id.as_bytes());
+        self.upload_image(&image_id_hex, elf).await?;
+        let input_id = self.upload("inputs", input.bytes().to_vec()).await?;
+
+        let mut assumptions = Vec::new();
+        for receipt in input.assumptions() {
+            let inner = InnerAssumptionReceipt::from(receipt.inner.clone());
+            if !matches!(inner, InnerAssumptionReceipt::Succinct(_)) {
+                return Err("Bonsai only accepts succinct receipts as assumptions".to_string());
+            }
+            let bytes = bincode::serialize(&inner).map_err(|e| e.to_string())?;
+            assumptions.push(self.upload("receipts", bytes).await?);
+        }
+
+        let request = ProofRequest {
+            img: &image_id_hex,
+            input: &input_id,
+            assumptions: &assumptions,
+            execute_only: false,
+            exec_cycle_limit: input.session_limit(),
+        };
+        let session: CreatedSession = Self::json(
+            self.send(|| {
+                self.client
+                    .post(self.endpoint("sessions/create"))
+                    .json(&request)
+            })
+            .await?,
+        )
+        .await?;
+        debug!("Bonsai session {}", session.uuid);
+
+        let mut running = RunningSession {
+            client: self.client.clone(),
+            stop_url: self.endpoint(&format!("sessions/stop/{}", session.uuid)),
+            uuid: session.uuid.clone(),
+            awaited: false,
+        };
+        let status = self.wait_for("sessions", &session.uuid).await;
+        running.awaited = true;
+        let status = status?;
+        let stats = status
+            .stats
+            .map(|stats| CycleStats {
+                segments: stats.segments,
+                total_cycles: stats.total_cycles,
+                user_cycles: stats.cycles,
+                ..CycleStats::default()
+            })
+            .unwrap_or_default();
+        let receipt_url = status
+            .receipt_url
+            .ok_or("Bonsai session succeeded without a receipt")?;
+
+        if opts.receipt_kind != risc0_zkvm::ReceiptKind::Groth16 {
+            let receipt = self.download_receipt(&receipt_url).await?;
+            verify_proven(&receipt, image_id, opts)?;
+            return Ok(ProvingRun { receipt, stats });
+        }
+
+        let request = SnarkRequest {
+            session_id: &session.uuid,
+        };
+        let snark: CreatedSession = Self::json(
+            self.send(|| {
+                self.client
+                    .post(self.endpoint("snark/create"))
+                    .json(&request)
+            })
+            .await?,
+        )
+        .await?;
+        let status = self.wait_for("snark", &snark.uuid).await?;
+        let output = status
+            .output
+            .ok_or("Bonsai SNARK session succeeded without a receipt")?;
+        let receipt = self.download_receipt(&output).await?;
+        verify_proven(&receipt, image_id, opts)?;
+        Ok(ProvingRun { receipt, stats })
+    }
+}
+
+/// Proving session that is stopped if the future polling it is dropped before
+/// it ended, e.g. after a timeout. SNARK sessions cannot be stopped.
+struct RunningSession {
+    client: reqwest::Client,
+    stop_url: String,
+    uuid: String,
+    awaited: bool,
+}
+
+impl Drop for RunningSession {
+    fn drop(&mut self) {
+        if self.awaited {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let request = self.client.get(&self.stop_url);
+        let uuid = std::mem::take(&mut self.uuid);
+        runtime.spawn(async move {
+            match request.send().await.and_then(Response::error_for_status) {
+                Ok(_) => info!("Stopped abandoned Bonsai session {}", uuid),
+                Err(e) => warn!("Failed to stop abandoned Bonsai session {}: {}", uuid, e),
+            }
+        });
+    }
+}
+
+/// Bonsai also proves guests that halted with an error, so the exit code is
+/// only accepted as the local prover would with `opts`.
+fn verify_proven(receipt: &Receipt, image_id: Digest, opts: &ProverOpts) -> Result<(), String> {
+    let ctx = VerifierContext::default().with_dev_mode(opts.dev_mode());
+    let result = if opts.prove_guest_errors {
+        receipt.verify_integrity_with_context(&ctx)
+    } else {
+        receipt.verify_with_context(&ctx, image_id)
+    };
+    result.map_err(|e| format!("Invalid receipt from Bonsai: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        routing::{get, post, put},
+        Json, Router,
+    };
+    use proving_service_core::journal::REJECTED_EXIT_CODE;
+    use risc0_zkvm::{ExitCode, FakeReceipt, InnerReceipt, ReceiptClaim};
+    use serde_json::json;
+    use std::fs;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    /// Requests seen by `mock_bonsai`.
+    #[derive(Default)]
+    struct MockBonsai {
+        input: Vec<u8>,
+        session_requests: u32,
+        status_requests: u32,
+        stop_requests: u32,
+    }
+
+    /// Bonsai API whose first session request fails with 503 and whose session
+    /// runs for one poll before returning `receipt`.
+    async fn mock_bonsai(receipt: Vec<u8>) -> (String, Arc<Mutex<MockBonsai>>) {
+        type Mock = State<(String, Arc<Mutex<MockBonsai>>)>;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(MockBonsai::default()));
+
+        let app = Router::new()
+            .route(
+                "/images/upload/{image_id}",
+                get(|| async { StatusCode::NO_CONTENT }),
+            )
+            .route(
+                "/inputs/upload",
+                get(|State((url, _)): Mock| async move {
+                    Json(json!({ "url": format!("{}/upload/input", url), "uuid": "input" }))
+                }),
+            )
+            .route(
+                "/upload/input",
+                put(|State((_, seen)): Mock, body: Bytes| async move {
+                    seen.lock().unwrap().input = body.to_vec();
+                }),
+            )
+            .route(
+                "/sessions/create",
+                post(|State((_, seen)): Mock| async move {
+                    let mut seen = seen.lock().unwrap();
+                    seen.session_requests += 1;
+                    if seen.session_requests == 1 {
+                        return Err(StatusCode::SERVICE_UNAVAILABLE);
+                    }
+                    Ok(Json(json!({ "uuid": "session" })))
+                }),
+            )
+            .route(
+                "/sessions/status/session",
+                get(|State((url, seen)): Mock| async move {
+                    let mut seen = seen.lock().unwrap();
+                    seen.status_requests += 1;
+                    if seen.status_requests == 1 {
+                        return Json(json!({ "status": "RUNNING" }));
+                    }
+                    Json(json!({
+                        "status": "SUCCEEDED",
+                        "receipt_url": format!("{}/receipt", url),
+                        "stats": { "segments": 2, "total_cycles": 2097152, "cycles": 1500000 }
+                    }))
+                }),
+            )
+            .route(
+                "/sessions/stop/session",
+                get(|State((_, seen)): Mock| async move {
+                    seen.lock().unwrap().stop_requests += 1;
+                }),
+            )
+            .route("/receipt", get(move || async move { receipt }))
+            .with_state((url.clone(), seen.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, seen)
+    }
+
+    #[tokio::test]
+    async fn bonsai_backend_retries_and_polls() {
+        let journal = risc0_zkvm::serde::to_vec(&42.0f64).unwrap();
+        let journal: Vec<u8> = bytemuck::cast_slice(&journal).to_vec();
+        let receipt = Receipt::new(
+            InnerReceipt::Fake(FakeReceipt::new(ReceiptClaim::ok(
+                Digest::ZERO,
+                journal.clone(),
+            ))),
+            journal.clone(),
+        );
+        let (url, seen) = mock_bonsai(bincode::serialize(&receipt).unwrap()).await;
+
+        let client = BonsaiClient::new(&url, "key", Duration::from_secs(5))
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(10));
+        let mut input = GuestInput::new();
+        input.write(&"document").unwrap();
+        let opts = ProverOpts::succinct().with_dev_mode(true);
+        let run = client
+            .prove(&[], Digest::ZERO, &input, &opts)
+            .await
+            .unwrap();
+
+        assert_eq!(run.receipt.journal.bytes, journal);
+        assert_eq!(run.stats.segments, 2);
+        assert_eq!(run.stats.user_cycles, 1500000);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.input, input.bytes());
+        assert_eq!(seen.session_requests, 2);
+        assert_eq!(seen.status_requests, 2);
+    }
+
+    #[tokio::test]
+    async fn bonsai_backend_accepts_guest_errors_on_request() {
+        let journal = risc0_zkvm::serde::to_vec(&"rejected").unwrap();
+        let journal: Vec<u8> = bytemuck::cast_slice(&journal).to_vec();
+        let mut claim = ReceiptClaim::ok(Digest::ZERO, journal.clone());
+        claim.exit_code = ExitCode::Halted(REJECTED_EXIT_CODE.into());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+        let (url, _) = mock_bonsai(bincode::serialize(&receipt).unwrap()).await;
+
+        let client = BonsaiClient::new(&url, "key", Duration::from_secs(5))
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(10));
+        let opts = ProverOpts::succinct().with_dev_mode(true);
+        let input = GuestInput::new();
+        assert!(client
+            .prove(&[], Digest::ZERO, &input, &opts)
+            .await
+            .is_err());
+        let opts = opts.with_prove_guest_errors(true);
+        let run = client
+            .prove(&[], Digest::ZERO, &input, &opts)
+            .await
+            .unwrap();
+        assert_eq!(run.receipt.journal.bytes, receipt.journal.bytes);
+    }
+
+    #[tokio::test]
+    async fn bonsai_backend_gives_up_after_max_retries() {
+        let (url, seen) = mock_bonsai(Vec::new()).await;
+        let client = BonsaiClient::new(&url, "key", Duration::from_secs(5))
+            .unwrap()
+            .with_max_retries(0);
+        let result = client
+            .prove(
+                &[],
+                Digest::ZERO,
+                &GuestInput::new(),
+                &ProverOpts::succinct(),
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(seen.lock().unwrap().session_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn bonsai_backend_does_not_repeat_timed_out_sessions() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let session_requests = Arc::new(AtomicU32::new(0));
+        let seen = session_requests.clone();
+        let upload_url = format!("{}/upload/input", url);
+        let app = Router::new()
+            .route(
+                "/images/upload/{image_id}",
+                get(|| async { StatusCode::NO_CONTENT }),
+            )
+            .route(
+                "/inputs/upload",
+                get(move || async move { Json(json!({ "url": upload_url, "uuid": "input" })) }),
+            )
+            .route("/upload/input", put(|| async {}))
+            .route(
+                "/sessions/create",
+                post(move || async move {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Json(json!({ "uuid": "session" }))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = BonsaiClient::new(&url, "key", Duration::from_millis(200))
+            .unwrap()
+            .with_max_retries(2);
+        let result = client
+            .prove(
+                &[],
+                Digest::ZERO,
+                &GuestInput::new(),
+                &ProverOpts::succinct(),
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(session_requests.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn proving_times_out() {
+        // The session is still running when the timeout expires
+        let (url, seen) = mock_bonsai(Vec::new()).await;
+        let client = BonsaiClient::new(&url, "key", Duration::from_secs(5))
+            .unwrap()
+            .with_poll_interval(Duration::from_secs(60));
+        let timeout = Duration::from_millis(500);
+        let result = ProverBackend::Bonsai(client)
+            .prove(
+                &[],
+                Digest::ZERO,
+                &GuestInput::new(),
+                &ProverOpts::succinct(),
+                Some(timeout),
+            )
+            .await;
+
+        assert!(matches!(result, Err(ProverError::Timeout(t)) if t == timeout));
+
+        // The abandoned session is stopped in the background
+        for _ in 0..50 {
+            if seen.lock().unwrap().stop_requests > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.session_requests, 2);
+        assert_eq!(seen.stop_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn r0vm_is_killed_after_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for an r0vm server that never answers
+        let dir = std::env::temp_dir().join(format!("fake-r0vm-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("r0vm");
+        let pid_file = dir.join("pid");
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\n\
+                 if [ \"$1\" = --version ]; then echo \"r0vm {}\"; exit 0; fi\n\
+                 exec > /dev/null 2>&1\n\
+                 echo $$ > {}\n\
+                 while true; do sleep 0.1; done\n",
+                risc0_zkvm::VERSION,
+                pid_file.display()
+            ),
+        )?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+
+        let timeout = Duration::from_secs(2);
+        let result = ProverBackend::R0vm { path }
+            .prove(
+                &[],
+                Digest::ZERO,
+                &GuestInput::new(),
+                &ProverOpts::succinct(),
+                Some(timeout),
+            )
+            .await;
+        assert!(matches!(result, Err(ProverError::Timeout(t)) if t == timeout));
+
+        // Killed, and at most not yet reaped
+        let pid = fs::read_to_string(&pid_file)?;
+        let stat = Path::new("/proc").join(pid.trim()).join("stat");
+        let mut alive = true;
+        for _ in 0..50 {
+            alive = fs::read_to_string(&stat).is_ok_and(|stat| !stat.contains(") Z "));
+            if !alive {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        if alive {
+            std::process::Command::new("kill")
+                .arg(pid.trim())
+                .status()?;
+        }
+        fs::remove_dir_all(&dir)?;
+        assert!(!alive);
+        Ok(())
+    }
+
+    #[test]
+    fn headers_only_tighten_proving_limits() {
+        let mut limits = ProvingLimits {
+            cycles: Some(1 << 24),
+            timeout: Some(Duration::from_secs(600)),
+        };
+        limits.tighten_cycles(1 << 30);
+        limits.tighten_timeout(Duration::from_secs(60));
+        assert_eq!(limits.cycles, Some(1 << 24));
+        assert_eq!(limits.timeout, Some(Duration::from_secs(60)));
+
+        let mut unlimited = ProvingLimits {
+            cycles: None,
+            timeout: None,
+        };
+        unlimited.tighten_cycles(1 << 20);
+        assert_eq!(unlimited.cycles, Some(1 << 20));
+    }
+}
@@ -0,0 +1,75 @@
+use risc0_zkvm::{InnerReceipt, ProverOpts, Receipt};
+use tracing::warn;
+
+/// Kind of receipt produced by the prover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReceiptKind {
+    /// One STARK per segment (default); large, but cheapest to produce.
+    #[default]
+    Composite,
+    /// Segments recursively compressed into a single STARK of constant size.
+    Succinct,
+    /// Succinct receipt wrapped into a Groth16 SNARK; smallest, but cannot be
+    /// used as an assumption for composed proofs.
+    Groth16,
+}
+
+impl ReceiptKind {
+    /// Reads `RECEIPT_KIND` (`composite`, `succinct` or `groth16`).
+    pub fn from_env() -> Self {
+        std::env::var("RECEIPT_KIND")
+            .map(|kind| Self::parse(&kind))
+            .unwrap_or_default()
+    }
+
+    pub fn from_name(kind: &str) -> Option<Self> {
+        match kind {
+            "composite" => Some(ReceiptKind::Composite),
+            "succinct" => Some(ReceiptKind::Succinct),
+            "groth16" => Some(ReceiptKind::Groth16),
+            _ => None,
+        }
+    }
+
+    /// Parses a receipt kind, falling back to composite for unknown values.
+    pub fn parse(kind: &str) -> Self {
+        Self::from_name(kind).unwrap_or_else(|| {
+            warn!("Unknown receipt kind '{}', falling back to 'composite'", kind);
+            ReceiptKind::Composite
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReceiptKind::Composite => "composite",
+            ReceiptKind::Succinct => "succinct",
+            ReceiptKind::Groth16 => "groth16",
+        }
+    }
+
+    /// Groth16 wrapping is only supported on x86_64 hosts.
+    pub fn available(self) -> Self {
+        if self == ReceiptKind::Groth16 && !cfg!(target_arch = "x86_64") {
+            warn!("Groth16 receipts are not available on this platform, using succinct");
+            return ReceiptKind::Succinct;
+        }
+        self
+    }
+
+    pub fn prover_opts(&self) -> ProverOpts {
+        match self {
+            ReceiptKind::Composite => ProverOpts::composite(),
+            ReceiptKind::Succinct => ProverOpts::succinct(),
+            ReceiptKind::Groth16 => ProverOpts::groth16(),
+        }
+    }
+
+    pub fn of(receipt: &Receipt) -> Option<Self> {
+        match receipt.inner {
+            InnerReceipt::Composite(_) => Some(ReceiptKind::Composite),
+            InnerReceipt::Succinct(_) => Some(ReceiptKind::Succinct),
+            InnerReceipt::Groth16(_) => Some(ReceiptKind::Groth16),
+            _ => None,
+        }
+    }
+}
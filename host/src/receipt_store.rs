@@ -0,0 +1,396 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use proving_service_core::product_footprint::ProductProof;
+use risc0_zkvm::Receipt;
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+use sha2::{Digest as _, Sha256};
+use tokio::io::AsyncReadExt;
+use tracing::{error, warn};
+
+use crate::metrics;
+
+/// Largest receipt read from a store, well above composite receipts of long
+/// guest runs.
+const MAX_RECEIPT_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum StoreError {
+    UnsupportedUri(String),
+    Io(String),
+    NotFound(String),
+    TooLarge(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::UnsupportedUri(uri) => write!(f, "unsupported receipt URI: {}", uri),
+            StoreError::Io(e) => write!(f, "receipt store error: {}", e),
+            StoreError::NotFound(uri) => write!(f, "receipt not found: {}", uri),
+            StoreError::TooLarge(uri) => {
+                write!(f, "receipt exceeds {} bytes: {}", MAX_RECEIPT_BYTES, uri)
+            }
+        }
+    }
+}
+
+/// Storage for serialized receipts, addressed by the hex SHA-256 of their bytes.
+#[async_trait]
+pub trait ReceiptStore: Send + Sync {
+    /// Stores `bytes` under `digest` and returns the URI to put into `ProductProof`.
+    async fn put(&self, digest: &str, bytes: &[u8]) -> Result<String, StoreError>;
+
+    async fn get(&self, uri: &str) -> Result<Vec<u8>, StoreError>;
+}
+
+/// Receipts as files in a local directory, referenced as `file://<dir>/<digest>.bin`.
+pub struct FsReceiptStore {
+    root: PathBuf,
+}
+
+impl FsReceiptStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsReceiptStore { root: root.into() }
+    }
+
+    /// Resolves `uri` to a receipt file in `root`. URIs come from untrusted
+    /// messages, so anything but `file://<root>/<digest>.bin` is refused.
+    fn path_of(&self, uri: &str) -> Option<PathBuf> {
+        let path = Path::new(uri.strip_prefix("file://")?);
+        if path.parent()? != self.root {
+            return None;
+        }
+        let key = path.file_name()?.to_str()?;
+        is_receipt_key(key).then(|| self.root.join(key))
+    }
+}
+
+/// Receipts are stored as `<digest>.bin`, with the lowercase hex SHA-256 of
+/// their bytes as digest.
+fn is_receipt_key(key: &str) -> bool {
+    key.strip_suffix(".bin").is_some_and(|digest| {
+        digest.len() == 64
+            && digest
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    })
+}
+
+#[async_trait]
+impl ReceiptStore for FsReceiptStore {
+    async fn put(&self, digest: &str, bytes: &[u8]) -> Result<String, StoreError> {
+        tokio::fs::create_dir_all(&self.root)
+            .await
+            .map_err(|e| StoreError::Io(e.to_string()))?;
+        let path = self.root.join(format!("{}.bin", digest));
+        tokio::fs::write(&path, bytes)
+            .await
+            .map_err(|e| StoreError::Io(e.to_string()))?;
+        Ok(format!("file://{}", path.display()))
+    }
+
+    async fn get(&self, uri: &str) -> Result<Vec<u8>, StoreError> {
+        let path = self
+            .path_of(uri)
+            .ok_or_else(|| StoreError::UnsupportedUri(uri.to_string()))?;
+        let io_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::NotFound => StoreError::NotFound(uri.to_string()),
+            _ => StoreError::Io(e.to_string()),
+        };
+        let file = tokio::fs::File::open(&path).await.map_err(io_error)?;
+        let metadata = file.metadata().await.map_err(io_error)?;
+        if !metadata.is_file() {
+            return Err(StoreError::UnsupportedUri(uri.to_string()));
+        }
+        if metadata.len() > MAX_RECEIPT_BYTES {
+            return Err(StoreError::TooLarge(uri.to_string()));
+        }
+
+        // The file may grow after the size check
+        let mut bytes = Vec::with_capacity(metadata.len() as usize);
+        file.take(MAX_RECEIPT_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(io_error)?;
+        if bytes.len() as u64 > MAX_RECEIPT_BYTES {
+            return Err(StoreError::TooLarge(uri.to_string()));
+        }
+        Ok(bytes)
+    }
+}
+
+/// Receipts in an S3 compatible bucket (e.g. MinIO), referenced as `s3://<bucket>/<digest>.bin`.
+pub struct S3ReceiptStore {
+    bucket: Box<Bucket>,
+}
+
+impl S3ReceiptStore {
+    pub fn new(
+        endpoint: &str,
+        region: &str,
+        bucket: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Result<Self, StoreError> {
+        let region = Region::Custom {
+            region: region.to_string(),
+            endpoint: endpoint.to_string(),
+        };
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)
+            .map_err(|e| StoreError::Io(e.to_string()))?;
+        let bucket = Bucket::new(bucket, region, credentials)
+            .map_err(|e| StoreError::Io(e.to_string()))?
+            .with_path_style();
+        Ok(S3ReceiptStore { bucket })
+    }
+}
+
+#[async_trait]
+impl ReceiptStore for S3ReceiptStore {
+    async fn put(&self, digest: &str, bytes: &[u8]) -> Result<String, StoreError> {
+        let key = format!("{}.bin", digest);
+        let response = self
+            .bucket
+            .put_object(&key, bytes)
+            .await
+            .map_err(|e| StoreError::Io(e.to_string()))?;
+        if response.status_code() >= 300 {
+            return Err(StoreError::Io(format!(
+                "upload of {} failed with status {}",
+                key,
+                response.status_code()
+            )));
+        }
+        Ok(format!("s3://{}/{}", self.bucket.name(), key))
+    }
+
+    /// URIs come from untrusted messages, so only receipt keys in the bucket are
+    /// read, and no more than `MAX_RECEIPT_BYTES` of them.
+    async fn get(&self, uri: &str) -> Result<Vec<u8>, StoreError> {
+        let key = uri
+            .strip_prefix(&format!("s3://{}/", self.bucket.name()))
+            .filter(|key| is_receipt_key(key))
+            .ok_or_else(|| StoreError::UnsupportedUri(uri.to_string()))?;
+        let s3_error = |e: S3Error| match e {
+            S3Error::HttpFailWithBody(404, _) => StoreError::NotFound(uri.to_string()),
+            e => StoreError::Io(e.to_string()),
+        };
+
+        let (head, _) = self.bucket.head_object(key).await.map_err(s3_error)?;
+        if head.content_length.unwrap_or_default() > MAX_RECEIPT_BYTES as i64 {
+            return Err(StoreError::TooLarge(uri.to_string()));
+        }
+
+        // The object may be replaced after the size check; the range end is inclusive
+        let response = self
+            .bucket
+            .get_object_range(key, 0, Some(MAX_RECEIPT_BYTES))
+            .await
+            .map_err(s3_error)?;
+        match response.status_code() {
+            200 | 206 if response.bytes().len() as u64 > MAX_RECEIPT_BYTES => {
+                Err(StoreError::TooLarge(uri.to_string()))
+            }
+            200 | 206 => Ok(response.bytes().to_vec()),
+            404 => Err(StoreError::NotFound(uri.to_string())),
+            status => Err(StoreError::Io(format!(
+                "download of {} failed with status {}",
+                uri, status
+            ))),
+        }
+    }
+}
+
+static STORE: LazyLock<Result<Option<Box<dyn ReceiptStore>>, StoreError>> = LazyLock::new(from_env);
+
+/// Reads `RECEIPT_STORE` (`inline`, `fs` or `s3`). With `inline` (default)
+/// receipts stay base64 encoded in `ProductProof.proofReceipt`.
+fn from_env() -> Result<Option<Box<dyn ReceiptStore>>, StoreError> {
+    let env = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+    match env("RECEIPT_STORE", "inline").as_str() {
+        "fs" => Ok(Some(Box::new(FsReceiptStore::new(env(
+            "RECEIPT_STORE_PATH",
+            "receipts",
+        ))))),
+        "s3" => {
+            let store = S3ReceiptStore::new(
+                &env("S3_ENDPOINT", "http://localhost:9000"),
+                &env("S3_REGION", "us-east-1"),
+                &env("S3_BUCKET", "receipts"),
+                &env("S3_ACCESS_KEY", ""),
+                &env("S3_SECRET_KEY", ""),
+            )
+            .inspect_err(|e| error!("Failed to set up S3 receipt store: {}", e))?;
+            Ok(Some(Box::new(store)))
+        }
+        "inline" => Ok(None),
+        other => {
+            warn!("Unknown RECEIPT_STORE '{}', keeping receipts inline", other);
+            Ok(None)
+        }
+    }
+}
+
+pub fn global() -> Option<&'static dyn ReceiptStore> {
+    STORE.as_ref().ok()?.as_deref()
+}
+
+/// Why the configured receipt store could not be set up. Receipts are then
+/// neither stored nor inlined, so jobs fail instead of silently growing messages.
+pub fn setup_error() -> Option<&'static StoreError> {
+    STORE.as_ref().err()
+}
+
+fn content_digest(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Receipt fields of a `ProductProof`.
+pub struct StoredReceipt {
+    /// Base64 receipt, empty if the receipt lives in the store.
+    pub proof_receipt: String,
+    pub receipt_uri: Option<String>,
+    pub receipt_digest: String,
+}
+
+/// Serializes `receipt` and saves it to the configured store, or inlines it.
+pub async fn store_receipt(receipt: &Receipt) -> Option<StoredReceipt> {
+    let receipt_bytes = match bincode::serialize(receipt) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to serialize receipt: {}", e);
+            return None;
+        }
+    };
+    let receipt_digest = content_digest(&receipt_bytes);
+    metrics::observe_receipt_size(receipt_bytes.len());
+
+    if let Some(e) = setup_error() {
+        error!("Failed to store receipt: {}", e);
+        return None;
+    }
+    let Some(store) = global() else {
+        return Some(StoredReceipt {
+            proof_receipt: general_purpose::STANDARD.encode(receipt_bytes),
+            receipt_uri: None,
+            receipt_digest,
+        });
+    };
+
+    match store.put(&receipt_digest, &receipt_bytes).await {
+        Ok(uri) => Some(StoredReceipt {
+            proof_receipt: String::new(),
+            receipt_uri: Some(uri),
+            receipt_digest,
+        }),
+        Err(e) => {
+            error!("Failed to store receipt: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns the serialized receipt of `pcf_proof`, fetching it from the store
+/// if it is referenced by URI.
+pub async fn load_receipt_bytes(pcf_proof: &ProductProof) -> Option<Vec<u8>> {
+    let Some(uri) = &pcf_proof.receiptUri else {
+        return match general_purpose::STANDARD.decode(&pcf_proof.proofReceipt) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                error!(
+                    "Error while decoding receipt. Id: {}. {}",
+                    pcf_proof.productFootprintId, e
+                );
+                None
+            }
+        };
+    };
+
+    let Some(store) = global() else {
+        error!("Receipt {} is referenced by URI, but no receipt store is configured", uri);
+        return None;
+    };
+
+    let bytes = match store.get(uri).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to load receipt: {}", e);
+            return None;
+        }
+    };
+
+    if pcf_proof.receiptDigest.as_deref() != Some(content_digest(&bytes).as_str()) {
+        error!("Receipt at {} does not match its digest", uri);
+        return None;
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn s3_store_only_reads_receipt_keys() {
+        let store =
+            S3ReceiptStore::new("http://localhost:1", "us-east-1", "receipts", "", "").unwrap();
+        let digest = "ab".repeat(32);
+
+        for uri in [
+            format!("s3://other/{}.bin", digest),
+            "s3://receipts/../secrets/key.bin".to_string(),
+            format!("s3://receipts/nested/{}.bin", digest),
+            format!("s3://receipts/{}.bin", digest.to_uppercase()),
+            format!("s3://receipts/{}", digest),
+        ] {
+            assert!(
+                matches!(store.get(&uri).await, Err(StoreError::UnsupportedUri(_))),
+                "{} was accepted",
+                uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fs_receipt_store_only_reads_its_own_receipts() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let root = std::env::temp_dir().join(format!("receipt-store-{}", std::process::id()));
+        let store = FsReceiptStore::new(&root);
+        let digest = "ab".repeat(32);
+        let uri = store.put(&digest, b"receipt").await.unwrap();
+        assert_eq!(store.get(&uri).await.unwrap(), b"receipt");
+
+        std::fs::write(root.join("other.bin"), b"secret")?;
+        let dir = root.display();
+        for uri in [
+            "file:///dev/zero".to_string(),
+            "file:///etc/passwd".to_string(),
+            format!("file://{}/other.bin", dir),
+            format!("file://{}/../{}.bin", dir, digest),
+            format!("file://{}/{}.bin", dir, digest.to_uppercase()),
+            format!("s3://receipts/{}.bin", digest),
+        ] {
+            assert!(
+                matches!(store.get(&uri).await, Err(StoreError::UnsupportedUri(_))),
+                "{}",
+                uri
+            );
+        }
+        let missing = format!("file://{}/{}.bin", dir, "cd".repeat(32));
+        assert!(matches!(
+            store.get(&missing).await,
+            Err(StoreError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use rdkafka::message::{BorrowedHeaders, Headers};
+
+use crate::output::OutputMode;
+use crate::prover_backend::ProvingLimits;
+use crate::receipt_kind::ReceiptKind;
+use crate::wire_format::WireFormat;
+
+/// Per request settings. Defaults come from the environment and can be
+/// overridden per message via Kafka headers.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub output_mode: OutputMode,
+    /// Guest version to prove with; `None` selects the latest.
+    pub guest_version: Option<String>,
+    pub receipt_kind: ReceiptKind,
+    /// The payload is an `AggregationRequest` instead of a `ProofingDocument`.
+    pub aggregate: bool,
+    /// Execute the guest without proving and return a `DryRunReport`.
+    pub dry_run: bool,
+    /// Prove documents that fail validation, so the guest commits why they are
+    /// rejected, instead of dropping them on the host.
+    pub prove_rejections: bool,
+    /// Headers can only lower the limits from the environment.
+    pub limits: ProvingLimits,
+    /// Encoding of the incoming payload.
+    pub content_type: WireFormat,
+    /// Encoding of the result.
+    pub accept: WireFormat,
+    /// Identifies the job in logs and is copied to the result message.
+    pub correlation_id: Option<String>,
+}
+
+impl RequestOptions {
+    /// Reads `OUTPUT_MODE`, `GUEST_VERSION`, `RECEIPT_KIND`, `PROVE_REJECTIONS`,
+    /// `OUTPUT_CONTENT_TYPE` and the proving limits.
+    pub fn from_env() -> Self {
+        RequestOptions {
+            output_mode: OutputMode::from_env(),
+            guest_version: std::env::var("GUEST_VERSION").ok().filter(|v| !v.is_empty()),
+            receipt_kind: ReceiptKind::from_env(),
+            aggregate: false,
+            dry_run: false,
+            prove_rejections: std::env::var("PROVE_REJECTIONS").is_ok_and(|v| v == "true"),
+            limits: ProvingLimits::from_env(),
+            content_type: WireFormat::Json,
+            accept: WireFormat::output_from_env(),
+            correlation_id: None,
+        }
+    }
+
+    /// Applies the `output-mode`, `guest-version`, `receipt-kind`, `aggregate`,
+    /// `dry-run`, `prove-rejections`, `cycle-limit`, `timeout-secs`,
+    /// `content-type`, `accept` and `correlation-id` headers of a message.
+    pub fn with_headers(&self, headers: Option<&BorrowedHeaders>) -> Self {
+        let mut options = self.clone();
+        let Some(headers) = headers else {
+            return options;
+        };
+
+        for header in headers.iter() {
+            let value = match header.value.map(std::str::from_utf8) {
+                Some(Ok(value)) => value,
+                _ => continue,
+            };
+            match header.key {
+                "output-mode" => options.output_mode = OutputMode::parse(value),
+                "guest-version" => options.guest_version = Some(value.to_string()),
+                "receipt-kind" => options.receipt_kind = ReceiptKind::parse(value),
+                "aggregate" => options.aggregate = value == "true",
+                "dry-run" => options.dry_run = value == "true",
+                "prove-rejections" => options.prove_rejections = value == "true",
+                "cycle-limit" => {
+                    if let Ok(cycles) = value.parse() {
+                        options.limits.tighten_cycles(cycles);
+                    }
+                }
+                "timeout-secs" => {
+                    if let Ok(secs) = value.parse() {
+                        options.limits.tighten_timeout(Duration::from_secs(secs));
+                    }
+                }
+                "content-type" => options.content_type = WireFormat::parse(value),
+                "accept" => options.accept = WireFormat::parse(value),
+                "correlation-id" => options.correlation_id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        options
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::LazyLock;
+
+use jsonschema::JSONSchema;
+use proving_service_core::schema::proofing_document_schema;
+use serde_json::Value;
+
+static PROOFING_DOCUMENT_SCHEMA: LazyLock<JSONSchema> = LazyLock::new(|| {
+    let schema =
+        serde_json::to_value(proofing_document_schema()).expect("Failed to serialize schema");
+    JSONSchema::compile(&schema).expect("ProofingDocument schema is invalid")
+});
+
+/// Validates a raw proving document against the `ProofingDocument` schema.
+///
+/// Returns all violations instead of stopping at the first one. Semantic
+/// checks such as TOC/HOC references are done by `validation::validate`.
+pub fn validate_proofing_document(document: &Value) -> Vec<String> {
+    match PROOFING_DOCUMENT_SCHEMA.validate(document) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|e| format!("'{}': {}", e.instance_path, e))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sig_verifier::verify_signature;
+    use proving_service_core::validation::validate;
+    use proving_service_test_support::{ChainSpec, DocumentGenerator};
+
+    #[test]
+    fn generated_documents_are_valid() -> Result<(), Box<dyn std::error::Error>> {
+        let spec = ChainSpec::new(5, 4);
+        let proving_document = DocumentGenerator::new(7).generate(&spec);
+        let json = serde_json::to_value(&proving_document)?;
+
+        assert!(validate_proofing_document(&json).is_empty());
+        assert!(validate(&proving_document).is_empty());
+        let signed_sensor_data = proving_document.signedSensorData.as_deref().unwrap_or_default();
+        assert_eq!(signed_sensor_data.len(), 5);
+        for sensor_data in signed_sensor_data {
+            assert!(verify_signature(
+                &sensor_data.commitment,
+                &sensor_data.signedSensorData,
+                &sensor_data.sensorkey
+            ));
+        }
+
+        // The same seed reproduces the document byte for byte
+        let again = DocumentGenerator::new(7).generate(&spec);
+        assert_eq!(serde_json::to_value(&again)?, json);
+        Ok(())
+    }
+}
@@ -1,13 +1,10 @@
 use base64::{ engine::general_purpose, Engine as _ };
-use rand::rngs::OsRng;
-use rsa::pkcs8::spki;
-use rsa::RsaPrivateKey;
 use rsa::{RsaPublicKey, pkcs1::DecodeRsaPublicKey, pkcs8::DecodePublicKey};
-use spki::EncodePublicKey; // Import only EncodePublicKey for to_public_key_pem
 use rsa::pkcs1v15::Pkcs1v15Sign;
 use sha2::{Sha256, Digest as Sha2DigestTrait};
 use const_oid::AssociatedOid;
-use pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey as _, ObjectIdentifier};
+use pkcs1::ObjectIdentifier;
+use tracing::{debug, error, warn};
 use digest::{
     self,
     Digest as DigestTrait,
@@ -111,18 +108,18 @@ pub fn verify_signature(commitment: &str, signed_sensor_data: &str, sensorkey: &
 
     let public_key = match RsaPublicKey::from_public_key_pem(public_key_pem) {
         Ok(pk) => {
-            println!("Signatur erfolgreich verifiziert");
+            debug!("Loaded SPKI public key");
             pk
         },
         Err(e) => {
-            eprintln!("Fehler beim Laden des Public Keys (SPKI erwartet): {:?}", e);
+            debug!("Public key is not SPKI encoded, trying PKCS#1: {:?}", e);
             match RsaPublicKey::from_pkcs1_pem(public_key_pem) {
                 Ok(pk_fallback) => {
-                    eprintln!("Warnung: Public Key wurde als PKCS#1 geladen, SPKI wird bevorzugt.");
+                    warn!("Loaded public key as PKCS#1, SPKI is preferred");
                     pk_fallback
                 },
                 Err(e_fallback) => {
-                    eprintln!("Fehler beim Laden des Public Keys auch als PKCS#1: {:?}", e_fallback);
+                    error!("Failed to load public key as SPKI or PKCS#1: {:?}", e_fallback);
                     return false;
                 }
             }
@@ -136,7 +133,7 @@ pub fn verify_signature(commitment: &str, signed_sensor_data: &str, sensorkey: &
     let signature = match general_purpose::STANDARD.decode(signature_b64) {
         Ok(sig) => sig,
         Err(e) => {
-            eprintln!("Fehler beim Dekodieren der Signatur: {:?}", e);
+            error!("Failed to decode signature: {:?}", e);
             return false;
         }
     };
@@ -144,46 +141,12 @@ pub fn verify_signature(commitment: &str, signed_sensor_data: &str, sensorkey: &
     let padding = Pkcs1v15Sign::new::<Sha256WithOid>();
     match public_key.verify(padding, &digest_val, &signature) {
         Ok(_) => {
-            println!("Signatur ist gültig.");
+            debug!("Signature is valid");
             true
         }
         Err(e) => {
-            eprintln!("Verifikation fehlgeschlagen: {:?}", e);
+            error!("Signature verification failed: {:?}", e);
             false
         }
     }
 }
-
-pub fn hash(data: &str) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    Update::update(&mut hasher, data.as_bytes());
-    let computed_hash = hasher.finalize();
-    let mut hash_array = [0u8; 32];
-    hash_array.copy_from_slice(&computed_hash);
-    hash_array
-}
-
-pub fn generate_key_pair() -> Result<(String, String), Box<dyn std::error::Error>> {
-    let mut rng = OsRng;
-    let bits = 2048; // Empfohlene Bitlänge für RSA
-
-    let private_key = RsaPrivateKey::new(&mut rng, bits)?;
-    let public_key = RsaPublicKey::from(&private_key);
-
-    let private_key_pem: String = private_key.to_pkcs1_pem(pkcs1::LineEnding::LF)?.to_string();
-    let public_key_pem: String = public_key.to_public_key_pem(pkcs1::LineEnding::LF)?;
-
-    Ok((private_key_pem, public_key_pem))
-}
-
-pub fn sign_data(data: &str, private_key_pem: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let private_key = RsaPrivateKey::from_pkcs1_pem(private_key_pem)?;
-    let mut hasher = Sha256::new();
-    Update::update(&mut hasher, data.as_bytes());
-    let digest_val = hasher.finalize();
-
-    let padding = Pkcs1v15Sign::new::<Sha256WithOid>();
-    let signature = private_key.sign(padding, &digest_val)?;
-
-    Ok(general_purpose::STANDARD.encode(signature))
-}
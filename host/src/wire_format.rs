@@ -0,0 +1,90 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+/// Encoding of Kafka payloads. JSON is the default; CBOR and postcard encode
+/// the document more compactly, but `ProductProof.proofReceipt` stays a base64
+/// string in every format. Large receipts are better kept in a receipt store.
+/// CBOR and postcard results are tagged with their variant, see `ProvingResponse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Cbor,
+    Postcard,
+}
+
+impl WireFormat {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        // Ignore parameters like `; charset=utf-8`
+        match content_type.split(';').next().unwrap_or_default().trim() {
+            "application/json" => Some(WireFormat::Json),
+            "application/cbor" => Some(WireFormat::Cbor),
+            "application/x-postcard" => Some(WireFormat::Postcard),
+            _ => None,
+        }
+    }
+
+    /// Parses a content type, falling back to JSON for unknown values.
+    pub fn parse(content_type: &str) -> Self {
+        Self::from_content_type(content_type).unwrap_or_else(|| {
+            warn!("Unknown content type '{}', falling back to JSON", content_type);
+            WireFormat::Json
+        })
+    }
+
+    /// Reads the content type used for results from `OUTPUT_CONTENT_TYPE`.
+    pub fn output_from_env() -> Self {
+        std::env::var("OUTPUT_CONTENT_TYPE")
+            .map(|content_type| Self::parse(&content_type))
+            .unwrap_or_default()
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::Cbor => "application/cbor",
+            WireFormat::Postcard => "application/x-postcard",
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            WireFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+                Ok(bytes)
+            }
+            WireFormat::Postcard => postcard::to_allocvec(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            WireFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            WireFormat::Cbor => ciborium::from_reader(bytes).map_err(|e| e.to_string()),
+            WireFormat::Postcard => postcard::from_bytes(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proving_service_core::proofing_document::ProofingDocument;
+
+    #[test]
+    fn wire_formats_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let json_content = std::fs::read_to_string("json-examples/test_3_1_1.json")?;
+        let proving_document: ProofingDocument = serde_json::from_str(&json_content)?;
+        let expected = serde_json::to_value(&proving_document)?;
+
+        for format in [WireFormat::Json, WireFormat::Cbor, WireFormat::Postcard] {
+            let bytes = format.encode(&proving_document)?;
+            let decoded: ProofingDocument = format.decode(&bytes)?;
+            assert_eq!(serde_json::to_value(&decoded)?, expected, "{:?}", format);
+        }
+        Ok(())
+    }
+}
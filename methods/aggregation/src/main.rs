@@ -0,0 +1,79 @@
+use proving_service_core::journal::AggregationJournal;
+use proving_service_core::proof_container::ProofContainer;
+use proving_service_core::sig_container::SignatureContainer;
+use risc0_zkvm::guest::env;
+use risc0_zkvm::sha::Digest;
+use std::io::Write;
+
+/// Writes a log line to stderr, which the host forwards into the job's span.
+fn log(message: &str) {
+    let _ = writeln!(env::stderr(), "{}", message);
+}
+
+fn main() {
+    // Read inputs
+    let serialized_proof_containers: Vec<u8> = env::read();
+    let proof_containers: Vec<ProofContainer> = bincode::deserialize(&serialized_proof_containers)
+        .expect("Aggregation: Failed to deserialize proof_containers");
+    let allowed_image_ids: Vec<Digest> = env::read();
+    // A guest cannot know its own image ID, so it is passed in and committed.
+    // Verifiers reject journals naming another ID than the real one.
+    let aggregation_image_id: Digest = env::read();
+
+    let mut pcf: f64 = 0.0;
+    let mut leaf_count: u64 = 0;
+    let mut sig_containers: Vec<SignatureContainer> = Vec::new();
+
+    for proof_container in &proof_containers {
+        let image_id = proof_container.image_id;
+        let journal = &proof_container.journal;
+
+        assert!(
+            allowed_image_ids.contains(&image_id),
+            "Aggregation: Image ID {} is not in the allow-list",
+            image_id
+        );
+        env::verify(image_id, journal.bytes.as_slice()).unwrap();
+
+        let serialized_sig_containers = if image_id == aggregation_image_id {
+            // Inner node of the aggregation tree
+            let inner: AggregationJournal = journal
+                .decode()
+                .expect("Aggregation: Failed to decode aggregation journal");
+            assert!(
+                inner.aggregation_image_id == aggregation_image_id
+                    && inner.allowed_image_ids == allowed_image_ids,
+                "Aggregation: Inner aggregation was built with a different configuration"
+            );
+            pcf += inner.pcf;
+            leaf_count += inner.leaf_count;
+            inner.sig_containers
+        } else {
+            // Leaf proof of the proofing guest
+            let (leaf_pcf, leaf_sig_containers): (f64, Vec<u8>) = journal
+                .decode()
+                .expect("Aggregation: Failed to decode journal");
+            pcf += leaf_pcf;
+            leaf_count += 1;
+            leaf_sig_containers
+        };
+
+        let containers: Vec<SignatureContainer> = bincode::deserialize(&serialized_sig_containers)
+            .expect("Aggregation: Failed to deserialize sig_containers");
+        sig_containers.extend(containers);
+    }
+
+    log(&format!(
+        "Aggregation: {} proofs with {} kg CO2e in total",
+        leaf_count, pcf
+    ));
+
+    env::commit(&AggregationJournal {
+        pcf,
+        sig_containers: bincode::serialize(&sig_containers)
+            .expect("Aggregation: Failed to serialize sig_containers"),
+        leaf_count,
+        allowed_image_ids,
+        aggregation_image_id,
+    });
+}
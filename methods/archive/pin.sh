@@ -0,0 +1,43 @@
+#!/usr/bin/env bash
+# Builds the proofing guest of an archived version and pins its ELF and image ID.
+#
+# Usage: methods/archive/pin.sh <version>
+#
+# The guest is rebuilt from the commit listed for <version> in guests.txt with
+# `cargo risczero build`, which builds in Docker so the image ID is reproducible.
+set -euo pipefail
+
+version="$1"
+archive_dir="$(cd "$(dirname "$0")" && pwd)"
+manifest="$archive_dir/guests.txt"
+
+commit="$(awk -v version="$version" '$1 == version { print $2 }' "$manifest")"
+if [ -z "$commit" ]; then
+  echo "Version $version is not listed in $manifest" >&2
+  exit 1
+fi
+
+worktree="$(mktemp -d)"
+git -C "$archive_dir" worktree add --detach "$worktree" "$commit"
+trap 'git -C "$archive_dir" worktree remove --force "$worktree"' EXIT
+
+output="$(cd "$worktree" && cargo risczero build --manifest-path methods/guest/Cargo.toml 2>&1 | tee /dev/stderr)"
+image_id="$(echo "$output" | grep -o 'ImageID: [0-9a-f]\{64\}' | head -n 1 | cut -d' ' -f2)"
+elf="$(find "$worktree/target/riscv-guest" -name 'guest_proofing_logic*.bin' | head -n 1)"
+if [ -z "$image_id" ] || [ -z "$elf" ]; then
+  echo "Could not find the guest ELF or its image ID in the build output" >&2
+  exit 1
+fi
+
+pinned="$(awk -v version="$version" '$1 == version { print $3 }' "$manifest")"
+if [ "$pinned" != "-" ] && [ "$pinned" != "$image_id" ]; then
+  echo "Rebuilt image ID $image_id does not match the pinned $pinned" >&2
+  exit 1
+fi
+
+cp "$elf" "$archive_dir/$version.bin"
+awk -v version="$version" -v image_id="$image_id" \
+  '$1 == version && $3 == "-" { sub(/-$/, image_id) } { print }' \
+  "$manifest" > "$manifest.tmp"
+mv "$manifest.tmp" "$manifest"
+echo "Pinned guest $version ($commit) with image ID $image_id"
@@ -1,3 +1,96 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Released guest versions, their commits and pinned image IDs.
+const ARCHIVE_MANIFEST: &str = "archive/guests.txt";
+
 fn main() {
     risc0_build::embed_methods();
+
+    let version = embed_guest_version("guest", "GUEST_PROOFING_LOGIC_VERSION");
+    embed_guest_version("aggregation", "GUEST_AGGREGATION_VERSION");
+    embed_archived_guests(&version);
+}
+
+/// Exposes the `[package] version` of the guest crate in `dir` as `env_name`.
+fn embed_guest_version(dir: &str, env_name: &str) -> String {
+    let manifest_path = format!("{}/Cargo.toml", dir);
+    println!("cargo:rerun-if-changed={}", manifest_path);
+    let manifest = fs::read_to_string(&manifest_path)
+        .unwrap_or_else(|_| panic!("Failed to read {}", manifest_path));
+    let version = manifest
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("version = "))
+        .map(|v| v.trim_matches('"'))
+        .unwrap_or_else(|| panic!("{} has no package version", manifest_path));
+    println!("cargo:rustc-env={}={}", env_name, version);
+    version.to_string()
+}
+
+/// Embeds the previously released guests pinned in `archive/guests.txt`, with
+/// their release binary `archive/<version>.bin` where one exists.
+fn embed_archived_guests(current_version: &str) {
+    println!("cargo:rerun-if-changed=archive");
+    let archive_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("archive");
+    let manifest = fs::read_to_string(ARCHIVE_MANIFEST)
+        .unwrap_or_else(|_| panic!("Failed to read {}", ARCHIVE_MANIFEST));
+
+    let mut versions: Vec<&str> = Vec::new();
+    let mut code = String::from("pub const ARCHIVED_GUESTS: &[ArchivedGuest] = &[\n");
+    for line in manifest.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let [version, _commit, image_id] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            panic!("Invalid line in {}: {}", ARCHIVE_MANIFEST, line);
+        };
+        if versions.contains(&version) {
+            panic!("{} lists guest {} twice", ARCHIVE_MANIFEST, version);
+        }
+        // A guest change without a version bump would reuse a released version
+        if parse_version(version) >= parse_version(current_version) {
+            panic!(
+                "{} lists guest {}, bump the version of the current guest {}",
+                ARCHIVE_MANIFEST, version, current_version
+            );
+        }
+        versions.push(version);
+
+        let elf_path = archive_dir.join(format!("{}.bin", version));
+        if image_id == "-" {
+            if elf_path.exists() {
+                panic!(
+                    "Pin the image ID of {} in {}",
+                    elf_path.display(),
+                    ARCHIVE_MANIFEST
+                );
+            }
+            continue;
+        }
+        let elf = if elf_path.exists() {
+            format!("include_bytes!({:?})", elf_path.display().to_string())
+        } else {
+            String::from("&[]")
+        };
+        code.push_str(&format!(
+            "    ArchivedGuest {{ version: {:?}, image_id: {:?}, elf: {} }},\n",
+            version, image_id, elf
+        ));
+    }
+    code.push_str("];\n");
+
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("archived_guests.rs");
+    fs::write(out_path, code).expect("Failed to write archived_guests.rs");
+}
+
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|part| {
+            part.parse()
+                .unwrap_or_else(|_| panic!("Invalid guest version {}", version))
+        })
+        .collect()
 }
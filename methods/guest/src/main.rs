@@ -1,29 +1,37 @@
 extern crate alloc;
 use bincode;
-use alloc::{ vec::Vec, string::String, format };
+use alloc::{ vec::Vec, format };
+use proving_service_core::calculation::{calculate_pcf, CalculationError};
+use proving_service_core::hashing::canonical_hash;
+use proving_service_core::journal::{AggregationJournal, ProofJournal, Rejection, REJECTED_EXIT_CODE};
+use proving_service_core::merkle::MerkleTree;
 use proving_service_core::proof_container::ProofContainer;
 use proving_service_core::sig_container::SignatureContainer;
 use risc0_zkvm::guest::env;
 use risc0_zkvm::Journal;
 use risc0_zkvm::sha::Digest;
-use sha2::{ Sha256, Digest as Sha2DigestTrait };
-use base64::{ engine::general_purpose, Engine as _ };
 use std::{ * };
 use proving_service_core::proofing_document::*;
-use proving_service_core::hoc_toc_data::*;
 use proving_service_core::product_footprint::*;
-use sha2::digest::{Update};
-
-fn hash(data: &str) -> String {
-    let mut hasher = Sha256::new();
-    Update::update(&mut hasher, data.as_bytes());
-    let computed_hash = hasher.finalize();
-    let computed_hash_b64 = general_purpose::STANDARD.encode(computed_hash);
-    return computed_hash_b64
+use std::io::Write;
+
+/// Writes a log line to stderr, which the host forwards into the job's span.
+fn log(message: &str) {
+    let _ = writeln!(env::stderr(), "{}", message);
+}
+
+/// Commits the reason instead of a journal and halts, so the receipt proves
+/// the rejection but cannot be verified as a successful proof.
+fn reject(error: &CalculationError) -> ! {
+    log(&format!("Guest: Rejecting document: {}", error));
+    env::commit(&Rejection::from(error));
+    env::exit(REJECTED_EXIT_CODE)
 }
 
 fn process_proof_containers(
     proof_containers: &[ProofContainer],
+    allowed_image_ids: &[Digest],
+    aggregation_image_id: &Digest,
     initial_transport_pcf: f64,
 ) -> f64 {
     let mut current_transport_pcf = initial_transport_pcf;
@@ -32,11 +40,30 @@ fn process_proof_containers(
         let image_id: Digest = proof_container.image_id.clone();
         let journal: Journal = proof_container.journal.clone();
 
+        assert!(
+            allowed_image_ids.contains(&image_id),
+            "Guest: Image ID {} is not in the allow-list",
+            image_id
+        );
+
         env::verify(image_id.clone(), journal.bytes.as_slice()).unwrap();
-        env::log(&format!("Guest: Image ID verified successfully: {}", image_id));
+        log(&format!("Guest: Image ID verified successfully: {}", image_id));
+
+        if image_id == *aggregation_image_id {
+            // An aggregation told another image ID summed that guest's journals as inner nodes
+            let aggregation: AggregationJournal = journal
+                .decode()
+                .expect("Failed to decode aggregation journal");
+            assert!(
+                aggregation.aggregation_image_id == image_id,
+                "Guest: Aggregation {} was built with image ID {}",
+                image_id,
+                aggregation.aggregation_image_id
+            );
+        }
 
         let pcf: f64 = journal.decode().expect("Failed to decode journal");
-        env::log(&format!("Guest: PCF value from previous proof: {}", pcf));
+        log(&format!("Guest: PCF value from previous proof: {}", pcf));
         current_transport_pcf = pcf + current_transport_pcf;
     }
 
@@ -45,93 +72,65 @@ fn process_proof_containers(
 
 fn main() {
     // Initialize
-    env::log("Guest: Starting the guest program...");
+    log("Guest: Starting the guest program...");
     let mut transport_pcf: f64 = 0.0;
 
     // Read inputs
-    env::log("Guest: Reading Inputs...");
+    log("Guest: Reading Inputs...");
     let mut sig_containers: Vec<SignatureContainer> = Vec::new();
     let product_footprint: ProofingDocument = env::read();
     let serialized_proof_containers: Vec<u8> = env::read();
     let proof_containers: Vec<ProofContainer> = bincode::deserialize(&serialized_proof_containers)
         .expect("Guest: Failed to deserialize proof_containers");
+    let allowed_image_ids: Vec<Digest> = env::read();
+    // Committed, as the guest cannot check that it is the real aggregation guest
+    let aggregation_image_id: Digest = env::read();
 
     // Verify previous proofs and add pcf value 
-    transport_pcf = process_proof_containers(&proof_containers, transport_pcf);
+    transport_pcf = process_proof_containers(
+        &proof_containers,
+        &allowed_image_ids,
+        &aggregation_image_id,
+        transport_pcf,
+    );
+
+    // Rejects documents without an iLEAP extension before it is accessed
+    let calculation = match calculate_pcf(&product_footprint, transport_pcf) {
+        Ok(calculation) => calculation,
+        Err(e) => reject(&e),
+    };
+    transport_pcf = calculation.pcf;
 
     let ileap_extension: &Extension = &product_footprint.productFootprint.extensions[0];
 
     let tces: &Vec<TCE> = &ileap_extension.data.tces;
 
-    for tce in tces {
-        if tce.tocId.is_some() {
-            if let Some(distance) = &tce.distance {
-                let emission_factor: f64 = emission_factor_toc(
-                    &product_footprint.tocData,
-                    tce.tocId.clone().unwrap()
-                );       
-
-                let emissions: f64 = tce.mass * emission_factor * distance.actual;
-
-                if let Some(signed_sensor_data_list) = &product_footprint.signedSensorData {
-                    for signed_sensor_data in signed_sensor_data_list {
-                        if signed_sensor_data.tceId == tce.tceId {
-                            let concat = format!("{}{}", serde_json::to_string(&signed_sensor_data.sensorData).unwrap(), signed_sensor_data.salt);
-                            assert!(hash(&concat) == signed_sensor_data.commitment, "Commitment does not match the hash of sensor data and salt");
-                            sig_containers.push(SignatureContainer {
-                                commitment: signed_sensor_data.commitment.clone(),
-                                signature: signed_sensor_data.signedSensorData.clone(),
-                                pub_key: signed_sensor_data.sensorkey.clone(),
-                            });
-                        }
-                    }
-                }
-
-                transport_pcf += emissions;
-            } else {
-                env::log("Distance is missing"); 
-            }
-        }
-
-        if tce.hocId.is_some() {
-            let emission_factor: f64 = emission_factor_hoc(
-                &product_footprint.hocData,
-                tce.hocId.clone().unwrap()
-            );
-            let emissions: f64 = tce.mass * emission_factor;
-            transport_pcf += emissions;
-        }
+    for signed_sensor_data in calculation.sensor_data {
+        sig_containers.push(SignatureContainer {
+            commitment: signed_sensor_data.commitment.clone(),
+            signature: signed_sensor_data.signedSensorData.clone(),
+            pub_key: signed_sensor_data.sensorkey.clone(),
+        });
     }
 
-    fn emission_factor_toc(toc_data: &Vec<TocData>, toc_id: String) -> f64 {
-        let right_toc_data: &TocData = toc_data
-            .into_iter()
-            .find(|t| { t.tocId == toc_id })
-            .unwrap();
-
-        let emission_factor_str: String = right_toc_data.co2eIntensityWTW.clone();
-
-        let factor = emission_factor_str.split(" ").next().unwrap().parse::<f64>().unwrap();
-
-        return factor;
-    }
-
-    fn emission_factor_hoc(hoc_data: &Vec<HocData>, hoc_id: String) -> f64 {
-        let right_hoc_data: &HocData = hoc_data
-            .into_iter()
-            .find(|t| { t.hocId == hoc_id })
-            .unwrap();
-
-        let emission_factor_str: String = right_hoc_data.co2eIntensityWTW.clone();
-
-        let factor = emission_factor_str.split(" ").next().unwrap().parse::<f64>().unwrap();
-
-        return factor;
-    }
-
-    env::log(&format!("Total Emissions {} kg CO2e", transport_pcf));
-    env::commit(&transport_pcf);
+    log(&format!("Total Emissions {} kg CO2e", transport_pcf));
     let serialized_sig_containers: Vec<u8> = bincode::serialize(&sig_containers)
         .expect("Failed to serialize sig_containers");
-    env::commit(&serialized_sig_containers);
+
+    // Bind the proof to the exact document it was computed from
+    let journal = ProofJournal {
+        pcf: transport_pcf,
+        sig_containers: serialized_sig_containers,
+        product_footprint_id: product_footprint.productFootprint.id.clone(),
+        shipment_id: ileap_extension.data.shipmentId.clone(),
+        document_hash: canonical_hash(&product_footprint),
+        toc_data_hash: canonical_hash(&product_footprint.tocData),
+        hoc_data_hash: canonical_hash(&product_footprint.hocData),
+        tce_root: MerkleTree::from_items(tces).root(),
+        toc_root: MerkleTree::from_items(&product_footprint.tocData).root(),
+        hoc_root: MerkleTree::from_items(&product_footprint.hocData).root(),
+        allowed_image_ids,
+        aggregation_image_id,
+    };
+    env::commit(&journal);
 }
@@ -1 +1,19 @@
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+
+/// Version of the guest program built from `guest/`, taken from its `Cargo.toml`.
+pub const GUEST_PROOFING_LOGIC_VERSION: &str = env!("GUEST_PROOFING_LOGIC_VERSION");
+
+/// Version of the aggregation guest built from `aggregation/`.
+pub const GUEST_AGGREGATION_VERSION: &str = env!("GUEST_AGGREGATION_VERSION");
+
+/// A previously released guest, pinned by image ID so older proofs stay
+/// verifiable. Its binary, where archived, lets it still be proven on request.
+pub struct ArchivedGuest {
+    pub version: &'static str,
+    /// Hex encoded like `ProductProof.imageId`.
+    pub image_id: &'static str,
+    /// Empty if only the image ID is pinned.
+    pub elf: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/archived_guests.rs"));
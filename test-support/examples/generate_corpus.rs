@@ -0,0 +1,38 @@
+//! Writes a corpus of synthetic proving documents:
+//!
+//! ```bash
+//! cargo run -p proving_service_test_support --example generate_corpus -- <dir> [count] [seed]
+//! ```
+//!
+//! Document `i` has between one and six transport legs and is reproducible
+//! from the seed alone.
+
+use std::fs;
+use std::path::Path;
+
+use proving_service_test_support::{ChainSpec, DocumentGenerator};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(dir) = args.get(1) else {
+        eprintln!("Usage: generate_corpus <dir> [count] [seed]");
+        std::process::exit(2);
+    };
+    let count: u32 = args
+        .get(2)
+        .map_or(100, |n| n.parse().expect("Invalid count"));
+    let seed: u64 = args.get(3).map_or(0, |n| n.parse().expect("Invalid seed"));
+
+    fs::create_dir_all(dir).expect("Failed to create corpus directory");
+    let mut generator = DocumentGenerator::new(seed);
+    for i in 0..count {
+        let transport_legs = 1 + i % 6;
+        let spec = ChainSpec::new(transport_legs, transport_legs.saturating_sub(1));
+        let document = generator.generate(&spec);
+
+        let path = Path::new(dir).join(format!("document_{:04}.json", i));
+        let json = serde_json::to_string_pretty(&document).expect("Failed to serialize document");
+        fs::write(&path, json + "\n").expect("Failed to write document");
+    }
+    println!("Wrote {} documents to {}", count, dir);
+}
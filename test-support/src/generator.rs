@@ -0,0 +1,486 @@
+use std::ops::Range;
+
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use chrono::{DateTime, SecondsFormat};
+use proving_service_core::hoc_toc_data::{
+    CertificationEnum, EnergyCarriers, HocData, TocData, TransportMode,
+};
+use proving_service_core::product_footprint::{
+    Distance, Extension, ExtensionData, ProductFootprint, TCE,
+};
+use proving_service_core::proofing_document::{ProofingDocument, SensorData, TceSensorData};
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use sha2::{Digest, Sha256};
+
+use crate::keys::FixtureKey;
+
+const ILEAP_DATA_SCHEMA: &str = "https://api.ileap.sine.dev/shipment-footprint.json";
+/// 2025-01-01T00:00:00Z, documents are created within the following year.
+const CREATED_FROM: i64 = 1_735_689_600;
+const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+const COMPANIES: [&str; 6] = [
+    "Nordic Freight AG",
+    "Rhine Logistics GmbH",
+    "Atlantic Shipping Ltd",
+    "Alpine Rail Cargo AG",
+    "Skyline Air Freight BV",
+    "Baltic Forwarding AB",
+];
+
+/// Shape of a generated transport chain.
+#[derive(Debug, Clone)]
+pub struct ChainSpec {
+    /// Transport legs, each with its own TOC.
+    pub transport_legs: u32,
+    /// Hub stops, each with its own HOC, placed between the transport legs.
+    pub hub_stops: u32,
+    /// Modes the main legs are drawn from; the first and last leg of chains
+    /// with three or more transport legs are road pre- and on-carriage.
+    pub modes: Vec<TransportMode>,
+    /// Transport legs, counted from the first, whose sensor data is signed
+    /// with the fixture keys.
+    pub signed_legs: u32,
+}
+
+impl ChainSpec {
+    /// Multi-modal chain with every transport leg signed.
+    pub fn new(transport_legs: u32, hub_stops: u32) -> Self {
+        ChainSpec {
+            transport_legs,
+            hub_stops,
+            modes: vec![
+                TransportMode::Road,
+                TransportMode::Rail,
+                TransportMode::Sea,
+                TransportMode::Air,
+            ],
+            signed_legs: transport_legs,
+        }
+    }
+
+    pub fn with_modes(mut self, modes: Vec<TransportMode>) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    pub fn with_signed_legs(mut self, signed_legs: u32) -> Self {
+        self.signed_legs = signed_legs;
+        self
+    }
+}
+
+/// Typical parameters of a transport mode, loosely following GLEC default values.
+struct ModeProfile {
+    descriptions: &'static [(&'static str, &'static str)],
+    distance_km: Range<f64>,
+    intensity_g_per_tkm: Range<f64>,
+    /// Ratio of the great circle to the actual distance.
+    detour: Range<f64>,
+    product_category_cpc: i32,
+}
+
+fn profile(mode: &TransportMode) -> ModeProfile {
+    match mode {
+        TransportMode::Road => ModeProfile {
+            descriptions: &[
+                ("Diesel truck 40t - long haul", "Diesel"),
+                ("HVO truck 40t - regional", "HVO100"),
+                ("Battery electric truck 18t - urban", "Electricity"),
+            ],
+            distance_km: 30.0..800.0,
+            intensity_g_per_tkm: 60.0..110.0,
+            detour: 0.75..0.95,
+            product_category_cpc: 6511,
+        },
+        TransportMode::Rail => ModeProfile {
+            descriptions: &[
+                ("Electric freight train - intermodal", "Electricity"),
+                ("Diesel freight train - single wagon", "Diesel"),
+            ],
+            distance_km: 150.0..1500.0,
+            intensity_g_per_tkm: 12.0..35.0,
+            detour: 0.75..0.9,
+            product_category_cpc: 6512,
+        },
+        TransportMode::Sea => ModeProfile {
+            descriptions: &[
+                ("Container vessel 8000 TEU - deep sea", "VLSFO"),
+                ("Container vessel 14000 TEU - deep sea", "LNG"),
+                ("Feeder vessel 1000 TEU - short sea", "MGO"),
+            ],
+            distance_km: 800.0..12000.0,
+            intensity_g_per_tkm: 5.0..16.0,
+            detour: 0.6..0.85,
+            product_category_cpc: 6521,
+        },
+        TransportMode::Air => ModeProfile {
+            descriptions: &[
+                ("Belly freight - long haul", "Jet A-1"),
+                ("Freighter - short haul", "Jet A-1"),
+            ],
+            distance_km: 400.0..9500.0,
+            intensity_g_per_tkm: 550.0..1100.0,
+            detour: 0.9..0.98,
+            product_category_cpc: 6531,
+        },
+    }
+}
+
+enum Leg {
+    Transport(u32),
+    Hub,
+}
+
+/// Generates realistic proving documents; the same seed always yields the same
+/// documents, including IDs, salts and signatures.
+pub struct DocumentGenerator {
+    rng: ChaCha8Rng,
+    seed: u64,
+}
+
+impl DocumentGenerator {
+    pub fn new(seed: u64) -> Self {
+        DocumentGenerator {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            seed,
+        }
+    }
+
+    /// A random UUID v4 drawn from the seeded generator.
+    fn uuid(&mut self) -> String {
+        let mut bytes = [0u8; 16];
+        self.rng.fill_bytes(&mut bytes);
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+
+    fn round(&mut self, range: Range<f64>, decimals: i32) -> f64 {
+        let factor = 10f64.powi(decimals);
+        (self.rng.gen_range(range) * factor).round() / factor
+    }
+
+    fn certifications(&mut self) -> Vec<CertificationEnum> {
+        let all = [
+            CertificationEnum::Iso14083_2023,
+            CertificationEnum::GleCv3,
+            CertificationEnum::GleCv3_1,
+        ];
+        all.into_iter().filter(|_| self.rng.gen_bool(0.5)).collect()
+    }
+
+    fn mode(&mut self, spec: &ChainSpec, index: u32) -> TransportMode {
+        let is_carriage = index == 0 || index + 1 == spec.transport_legs;
+        if spec.transport_legs >= 3 && is_carriage && spec.modes.contains(&TransportMode::Road) {
+            return TransportMode::Road;
+        }
+        spec.modes
+            .choose(&mut self.rng)
+            .cloned()
+            .unwrap_or(TransportMode::Road)
+    }
+
+    fn toc(&mut self, mode: TransportMode) -> TocData {
+        let profile = profile(&mode);
+        let (description, carrier) = *profile
+            .descriptions
+            .choose(&mut self.rng)
+            .expect("Mode without descriptions");
+        let wtw = self.round(profile.intensity_g_per_tkm.clone(), 1);
+        // Tank-to-wheel is the combustion share, nothing for electric vehicles
+        let ttw = if carrier == "Electricity" {
+            0.0
+        } else {
+            self.round(0.7..0.85, 2) * wtw
+        };
+        let (air_shipping_option, flight_length) = match mode {
+            TransportMode::Air if description.starts_with("Belly") => (
+                Some("belly freight".to_string()),
+                Some("long-haul".to_string()),
+            ),
+            TransportMode::Air => (
+                Some("freighter".to_string()),
+                Some("short-haul".to_string()),
+            ),
+            _ => (None, None),
+        };
+        let temperature_control = if self.rng.gen_bool(0.2) {
+            "refrigerated"
+        } else {
+            "ambient"
+        };
+        let truck_loading_sequence = match mode {
+            TransportMode::Road if self.rng.gen_bool(0.5) => "FTL",
+            TransportMode::Road => "LTL",
+            _ => "",
+        };
+
+        TocData {
+            tocId: self.uuid(),
+            certifications: self.certifications(),
+            description: description.to_string(),
+            mode,
+            loadFactor: format!("{:.2}", self.rng.gen_range(0.5..0.95)),
+            emptyDistanceFactor: format!("{:.2}", self.rng.gen_range(0.05..0.35)),
+            temperatureControl: temperature_control.to_string(),
+            truckLoadingSequence: truck_loading_sequence.to_string(),
+            airShippingOption: air_shipping_option,
+            flightLength: flight_length,
+            energyCarriers: vec![EnergyCarriers {
+                energyCarrier: carrier.to_string(),
+                relativeShare: "1.0".to_string(),
+                emissionFactorWTW: format!("{:.1} gCO2e/tkm", wtw),
+                emissionFactorTTW: format!("{:.1} gCO2e/tkm", ttw),
+            }],
+            co2eIntensityWTW: format!("{:.1} gCO2e/tkm", wtw),
+            co2eIntensityTTW: format!("{:.1} gCO2e/tkm", ttw),
+            transportActivityUnit: "tkm".to_string(),
+        }
+    }
+
+    fn hoc(&mut self) -> HocData {
+        let (passhub_type, carrier) = *[
+            ("Transshipment", "Electricity"),
+            ("Storage and Transshipment", "Electricity"),
+            ("Maritime Container Terminal", "Diesel"),
+            ("Air Cargo Terminal", "Natural Gas"),
+        ]
+        .choose(&mut self.rng)
+        .expect("No hub types");
+        let wtw = self.round(1.5..25.0, 1);
+        let ttw = if carrier == "Electricity" {
+            0.0
+        } else {
+            self.round(0.6..0.8, 2) * wtw
+        };
+
+        HocData {
+            hocId: self.uuid(),
+            passhubType: passhub_type.to_string(),
+            energyCarriers: vec![EnergyCarriers {
+                energyCarrier: carrier.to_string(),
+                relativeShare: "1.0".to_string(),
+                emissionFactorWTW: format!("{:.1} gCO2e/t", wtw),
+                emissionFactorTTW: format!("{:.1} gCO2e/t", ttw),
+            }],
+            co2eIntensityWTW: format!("{:.1} gCO2e/t", wtw),
+            co2eIntensityTTW: format!("{:.1} gCO2e/t", ttw),
+            hubActivityUnit: "tonnes".to_string(),
+        }
+    }
+
+    fn signed_sensor_data(
+        &mut self,
+        tce_id: &str,
+        distance: &Distance,
+        key: &FixtureKey,
+    ) -> TceSensorData {
+        let sensor_data = SensorData {
+            distance: distance.clone(),
+        };
+        let mut salt = [0u8; 32];
+        self.rng.fill_bytes(&mut salt);
+        let salt = general_purpose::STANDARD.encode(salt);
+
+        let sensor_json =
+            serde_json::to_string(&sensor_data).expect("Failed to serialize sensor data");
+        let commitment = general_purpose::STANDARD.encode(Sha256::digest(
+            format!("{}{}", sensor_json, salt).as_bytes(),
+        ));
+
+        TceSensorData {
+            tceId: tce_id.to_string(),
+            sensorkey: key.public_key_pem().to_string(),
+            signedSensorData: key.sign(&commitment),
+            sensorData: sensor_data,
+            commitment,
+            salt,
+        }
+    }
+
+    /// Generates one shipment along a chain shaped by `spec`.
+    pub fn generate(&mut self, spec: &ChainSpec) -> ProofingDocument {
+        let shipment_id = format!("SHIP_{}", self.uuid());
+        let mass = self.round(500.0..25000.0, 1);
+
+        // Transport legs alternate with hub stops, left-over stops come last
+        let mut legs = Vec::new();
+        for i in 0..spec.transport_legs.max(spec.hub_stops) {
+            if i < spec.transport_legs {
+                legs.push(Leg::Transport(i));
+            }
+            if i < spec.hub_stops {
+                legs.push(Leg::Hub);
+            }
+        }
+
+        let mut tces: Vec<TCE> = Vec::new();
+        let mut toc_data = Vec::new();
+        let mut hoc_data = Vec::new();
+        let mut signed_sensor_data = Vec::new();
+        let mut longest_leg: Option<(f64, TransportMode)> = None;
+
+        for leg in legs {
+            let tce_id = self.uuid();
+            let prev_tce_ids = tces
+                .last()
+                .map(|t| vec![t.tceId.clone()])
+                .unwrap_or_default();
+
+            let (toc_id, hoc_id, distance) = match leg {
+                Leg::Transport(index) => {
+                    let mode = self.mode(spec, index);
+                    let profile = profile(&mode);
+                    let actual = self.round(profile.distance_km, 2);
+                    let gcd = self.round(profile.detour, 3) * actual;
+                    let distance = Distance {
+                        actual,
+                        gcd: Some((gcd * 100.0).round() / 100.0),
+                        sfd: None,
+                    };
+
+                    if index < spec.signed_legs {
+                        let keys = FixtureKey::all();
+                        let key = &keys[index as usize % keys.len()];
+                        signed_sensor_data.push(self.signed_sensor_data(&tce_id, &distance, key));
+                    }
+                    if longest_leg.as_ref().is_none_or(|(km, _)| actual > *km) {
+                        longest_leg = Some((actual, mode.clone()));
+                    }
+
+                    let toc = self.toc(mode);
+                    let toc_id = toc.tocId.clone();
+                    toc_data.push(toc);
+                    (Some(toc_id), None, Some(distance))
+                }
+                Leg::Hub => {
+                    let hoc = self.hoc();
+                    let hoc_id = hoc.hocId.clone();
+                    hoc_data.push(hoc);
+                    (None, Some(hoc_id), None)
+                }
+            };
+
+            tces.push(TCE {
+                tceId: tce_id,
+                prevTceIds: prev_tce_ids,
+                hocId: hoc_id,
+                tocId: toc_id,
+                shipmentId: shipment_id.clone(),
+                mass,
+                co2eWTW: None,
+                co2eTTW: None,
+                transportActivity: None,
+                distance,
+            });
+        }
+
+        let created =
+            DateTime::from_timestamp(CREATED_FROM + self.rng.gen_range(0..SECONDS_PER_YEAR), 0)
+                .expect("Timestamp out of range")
+                .to_rfc3339_opts(SecondsFormat::Secs, true);
+        let company_name = COMPANIES.choose(&mut self.rng).expect("No companies");
+        let product_category_cpc = longest_leg
+            .map(|(_, mode)| profile(&mode).product_category_cpc)
+            .unwrap_or(6511);
+
+        let product_footprint = ProductFootprint {
+            id: self.uuid(),
+            specVersion: "2.0.0".to_string(),
+            version: 0,
+            created,
+            status: "Active".to_string(),
+            companyName: company_name.to_string(),
+            companyIds: vec![format!("urn:uuid:{}", self.uuid())],
+            productDescription: format!(
+                "Logistics emissions related to shipment with ID {}",
+                shipment_id
+            ),
+            productIds: vec![format!(
+                "urn:pathfinder:product:customcode:vendor-assigned:{}",
+                self.uuid()
+            )],
+            productCategoryCpc: product_category_cpc,
+            productNameCompany: format!("Shipment with ID {}", shipment_id),
+            pcf: None,
+            comment: format!("Synthetic shipment generated from seed {}", self.seed),
+            extensions: vec![Extension {
+                specVersion: "2.0.0".to_string(),
+                dataSchema: ILEAP_DATA_SCHEMA.to_string(),
+                data: ExtensionData {
+                    mass,
+                    shipmentId: shipment_id,
+                    tces,
+                },
+            }],
+        };
+
+        ProofingDocument {
+            productFootprint: product_footprint,
+            tocData: toc_data,
+            hocData: hoc_data,
+            signedSensorData: Some(signed_sensor_data),
+            proof: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proving_service_core::validation::validate;
+
+    fn generate(seed: u64, spec: &ChainSpec) -> String {
+        let document = DocumentGenerator::new(seed).generate(spec);
+        serde_json::to_string(&document).unwrap()
+    }
+
+    #[test]
+    fn same_seed_yields_same_document() {
+        let spec = ChainSpec::new(3, 2);
+        assert_eq!(generate(7, &spec), generate(7, &spec));
+        assert_ne!(generate(7, &spec), generate(8, &spec));
+    }
+
+    #[test]
+    fn generated_documents_pass_validation() {
+        let mut generator = DocumentGenerator::new(42);
+        for (legs, hubs) in [(1, 0), (1, 1), (3, 2), (4, 6)] {
+            let document = generator.generate(&ChainSpec::new(legs, hubs));
+            let issues = validate(&document);
+            assert!(issues.is_empty(), "{legs} legs, {hubs} hubs: {issues:?}");
+
+            let tces = &document.productFootprint.extensions[0].data.tces;
+            assert_eq!(tces.len(), (legs + hubs) as usize);
+            assert_eq!(document.tocData.len(), legs as usize);
+            assert_eq!(document.hocData.len(), hubs as usize);
+        }
+    }
+
+    #[test]
+    fn signs_the_requested_legs() {
+        let spec = ChainSpec::new(4, 0).with_signed_legs(2);
+        let document = DocumentGenerator::new(1).generate(&spec);
+        let signed = document.signedSensorData.unwrap();
+        assert_eq!(signed.len(), 2);
+
+        let tces = &document.productFootprint.extensions[0].data.tces;
+        let keys = FixtureKey::all();
+        for (i, sensor_data) in signed.iter().enumerate() {
+            assert_eq!(sensor_data.tceId, tces[i].tceId);
+            assert_eq!(sensor_data.sensorkey, keys[i % keys.len()].public_key_pem());
+        }
+    }
+}
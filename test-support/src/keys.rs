@@ -0,0 +1,92 @@
+use std::sync::LazyLock;
+
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+
+const FIXTURE_PEMS: [&str; 3] = [
+    include_str!("../fixtures/sensor-0.pem"),
+    include_str!("../fixtures/sensor-1.pem"),
+    include_str!("../fixtures/sensor-2.pem"),
+];
+
+static FIXTURE_KEYS: LazyLock<Vec<FixtureKey>> = LazyLock::new(|| {
+    FIXTURE_PEMS
+        .iter()
+        .map(|pem| FixtureKey::from_pkcs8_pem(pem))
+        .collect()
+});
+
+/// Sensor key pair checked into `fixtures/`. Test use only, the private keys
+/// are public.
+pub struct FixtureKey {
+    signing_key: SigningKey<Sha256>,
+    public_key_pem: String,
+}
+
+impl FixtureKey {
+    /// All fixture keys; documents cycle through them per transport leg.
+    pub fn all() -> &'static [FixtureKey] {
+        &FIXTURE_KEYS
+    }
+
+    fn from_pkcs8_pem(pem: &str) -> Self {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem).expect("Invalid fixture key");
+        let public_key_pem = RsaPublicKey::from(&private_key)
+            .to_public_key_pem(LineEnding::LF)
+            .expect("Failed to encode fixture public key");
+        FixtureKey {
+            signing_key: SigningKey::new(private_key),
+            public_key_pem,
+        }
+    }
+
+    /// SPKI PEM as expected in `TceSensorData.sensorkey`.
+    pub fn public_key_pem(&self) -> &str {
+        &self.public_key_pem
+    }
+
+    /// Base64 RSA PKCS#1 v1.5 SHA-256 signature, as verified by the host.
+    pub fn sign(&self, data: &str) -> String {
+        let signature = self.signing_key.sign(data.as_bytes());
+        general_purpose::STANDARD.encode(signature.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs1v15::{Signature, VerifyingKey};
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::signature::Verifier;
+
+    #[test]
+    fn signatures_verify_with_the_public_key() {
+        for key in FixtureKey::all() {
+            let public_key = RsaPublicKey::from_public_key_pem(key.public_key_pem()).unwrap();
+            let signature = general_purpose::STANDARD
+                .decode(key.sign("commitment"))
+                .unwrap();
+            let signature = Signature::try_from(signature.as_slice()).unwrap();
+
+            let verifying_key = VerifyingKey::<Sha256>::new(public_key);
+            assert!(verifying_key.verify(b"commitment", &signature).is_ok());
+            assert!(verifying_key.verify(b"tampered", &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn fixture_keys_are_distinct() {
+        let keys = FixtureKey::all();
+        assert_eq!(keys.len(), FIXTURE_PEMS.len());
+        for (i, key) in keys.iter().enumerate() {
+            for other in &keys[i + 1..] {
+                assert_ne!(key.public_key_pem(), other.public_key_pem());
+            }
+        }
+    }
+}
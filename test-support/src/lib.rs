@@ -0,0 +1,12 @@
+//! Deterministic synthetic proving documents for tests, benchmarks and corpora.
+//!
+//! ```ignore
+//! let mut generator = DocumentGenerator::new(42);
+//! let document = generator.generate(&ChainSpec::new(3, 2));
+//! ```
+
+pub mod generator;
+pub mod keys;
+
+pub use generator::{ChainSpec, DocumentGenerator};
+pub use keys::FixtureKey;